@@ -1,13 +1,50 @@
+mod check;
+mod duration;
+mod env;
+mod filter;
+mod migration;
+mod player;
+mod redaction;
+mod reload;
+
 use std::{
+    collections::{BTreeMap, BTreeSet},
     path::{Path, PathBuf},
     time::Duration,
     vec,
 };
 
-use clap::Parser;
+use anyhow::Context;
+use clap::{Parser, Subcommand, ValueEnum};
 use clap_verbosity_flag::Verbosity;
 use serde::{Deserialize, Serialize};
 
+pub use check::check_config;
+use env::Env;
+use filter::{Filter, FilterRule};
+use migration::Migration;
+pub use player::PlayerPolicy;
+use player::PlayerRule;
+pub use redaction::Redaction;
+use redaction::RedactionRule;
+pub use reload::Reloader;
+
+use crate::platform::{ExtraField, MediaData, PlaybackStatus};
+use crate::sink::SinkConfig;
+use crate::watcher::ReportMode;
+
+/// Friendly names for well-known players, matched as case-insensitive substrings.
+const BUILTIN_ALIASES: &[(&str, &str)] = &[
+    ("Microsoft.ZuneMusic", "Media Player"),
+    ("Microsoft.ZuneVideo", "Movies & TV"),
+    ("SpotifyAB.SpotifyMusic", "Spotify"),
+    ("Spotify.exe", "Spotify"),
+    ("Spotify.app", "Spotify"),
+    ("MSEdge", "Microsoft Edge"),
+    ("308046B0AF4A39CB", "Firefox"),
+    ("Mozilla Firefox", "Firefox"),
+];
+
 fn default_port() -> u16 {
     5600
 }
@@ -16,11 +53,27 @@ fn default_host() -> String {
     String::from("localhost")
 }
 
-fn default_poll_time() -> u64 {
-    5
+fn default_version() -> usize {
+    migration::CURRENT_VERSION
+}
+
+fn default_poll_time() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_bucket_id() -> String {
+    String::from(env!("CARGO_PKG_NAME"))
+}
+
+fn default_queue_size() -> usize {
+    10000
+}
+
+fn default_mpris_signals() -> bool {
+    true
 }
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[clap(author, version, about = "Watcher to report the currently playing media to ActivityWatch.", long_about = None)]
 pub struct Cli {
     #[arg(short, long, value_name = "FILE")]
@@ -36,6 +89,26 @@ pub struct Cli {
     #[clap(long)]
     port: Option<u16>,
 
+    /// Prefix of the bucket names, which are completed by the hostname.
+    /// Defaults to "aw-watcher-media-player" if not specified.
+    #[clap(long)]
+    bucket_id: Option<String>,
+
+    /// Hostname to report the data for, instead of the name of this machine.
+    /// Defaults to the system hostname if not specified.
+    #[clap(long)]
+    hostname: Option<String>,
+
+    /// Report every player to its own bucket, e.g. "aw-watcher-media-player-spotify_<hostname>".
+    /// Defaults to false if not specified.
+    #[clap(long)]
+    bucket_per_player: Option<bool>,
+
+    /// Report heartbeats on every poll, or one complete event per play once the media changes or stops.
+    /// Defaults to "heartbeat" if not specified.
+    #[clap(long)]
+    report_mode: Option<ReportMode>,
+
     /// Interval in seconds to request the currently playing media.
     /// Defaults to 5 if not specified.
     #[clap(long)]
@@ -52,70 +125,194 @@ pub struct Cli {
     #[clap(long, value_name = "PLAYERS", use_value_delimiter = true)]
     exclude_players: Vec<String>,
 
+    /// Comma-separated case-insensitive list of websites to report to ActivityWatch, e.g. "youtube.com".
+    /// If specified, only media from these websites and their subdomains is reported.
+    /// Media from all websites and players is reported if not specified.
+    #[clap(long, value_name = "SITES", use_value_delimiter = true)]
+    include_sites: Vec<String>,
+
+    /// Comma-separated case-insensitive list of websites to not report to ActivityWatch.
+    /// Media from these websites and their subdomains is not reported.
+    #[clap(long, value_name = "SITES", use_value_delimiter = true)]
+    exclude_sites: Vec<String>,
+
+    /// Linux only: subscribe to MPRIS D-Bus signals to report changes as soon as they happen.
+    /// Polling is used as a fallback if the signals are unavailable.
+    /// Defaults to true if not specified.
+    #[clap(long)]
+    mpris_signals: Option<bool>,
+
+    /// Report the time media spends paused to a separate bucket.
+    /// Defaults to false if not specified.
+    #[clap(long)]
+    report_paused: Option<bool>,
+
+    /// Comma-separated list of extra metadata fields to report if the player provides them.
+    /// No extra fields are reported if not specified.
+    #[clap(long, value_name = "FIELDS", use_value_delimiter = true)]
+    extra_fields: Vec<ExtraField>,
+
+    /// Treat players with the volume at zero as not playing, e.g. muted autoplaying tabs.
+    /// Defaults to false if not specified.
+    #[clap(long)]
+    ignore_muted: Option<bool>,
+
+    /// Maximum number of heartbeats to keep on disk while the server is unreachable.
+    /// They are sent in order once the server is back, 0 disables the queue.
+    /// Defaults to 10000 if not specified.
+    #[clap(long)]
+    queue_size: Option<usize>,
+
+    /// Play the media from a TOML or JSON timeline file instead of the real players, e.g. for testing.
+    #[arg(long, value_name = "FILE")]
+    mock_timeline: Option<PathBuf>,
+
+    /// Print the events which would be sent as JSON instead of sending them,
+    /// including the media which is filtered out and why.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Refuse to start if the configuration file has problems, such as unknown keys or invalid filters.
+    /// They are only logged otherwise.
+    #[clap(long)]
+    strict: bool,
+
     #[command(flatten)]
     pub verbosity: Verbosity,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Print the effective configuration merged from the command line, the environment and the file,
+    /// with the source of every value, and fail if the configuration has problems.
+    CheckConfig,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
 struct Toml {
+    /// Version of the configuration, older files are migrated to the current one.
+    #[serde(default = "default_version")]
+    version: usize,
     #[serde(default = "default_port")]
     port: u16,
     #[serde(default = "default_host")]
     host: String,
-    #[serde(default = "default_poll_time")]
-    poll_time: u64,
+    #[serde(default = "default_poll_time", with = "duration")]
+    poll_time: Duration,
+    #[serde(default = "default_bucket_id")]
+    bucket_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hostname: Option<String>,
+    #[serde(default)]
+    bucket_per_player: bool,
+    #[serde(default)]
+    report_mode: ReportMode,
     #[serde(default = "Vec::new")]
     include_players: Vec<String>,
     #[serde(default = "Vec::new")]
     exclude_players: Vec<String>,
+    #[serde(default = "Vec::new")]
+    include_sites: Vec<String>,
+    #[serde(default = "Vec::new")]
+    exclude_sites: Vec<String>,
+    #[serde(default = "default_mpris_signals")]
+    mpris_signals: bool,
+    #[serde(default)]
+    report_paused: bool,
+    #[serde(default = "Vec::new")]
+    extra_fields: Vec<ExtraField>,
+    #[serde(default)]
+    ignore_muted: bool,
+    #[serde(default = "default_queue_size")]
+    queue_size: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    filters: Vec<FilterRule>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    redactions: Vec<RedactionRule>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    players: Vec<PlayerRule>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    sinks: Vec<SinkConfig>,
+    #[serde(default)]
+    aliases: BTreeMap<String, String>,
+    /// Keys which are set in the file, to tell its values from the defaults.
+    #[serde(skip)]
+    file_keys: BTreeSet<String>,
+    /// Migration of a file with an older version, which is saved only when the watcher starts.
+    #[serde(skip)]
+    migration: Option<Migration>,
 }
 
 impl Default for Toml {
     fn default() -> Self {
         Self {
+            version: default_version(),
             port: default_port(),
             host: default_host(),
             poll_time: default_poll_time(),
+            bucket_id: default_bucket_id(),
+            hostname: None,
+            bucket_per_player: false,
+            report_mode: ReportMode::default(),
             include_players: Vec::new(),
             exclude_players: Vec::new(),
+            include_sites: Vec::new(),
+            exclude_sites: Vec::new(),
+            mpris_signals: default_mpris_signals(),
+            report_paused: false,
+            extra_fields: Vec::new(),
+            ignore_muted: false,
+            queue_size: default_queue_size(),
+            filters: Vec::new(),
+            redactions: Vec::new(),
+            players: Vec::new(),
+            sinks: Vec::new(),
+            aliases: BTreeMap::new(),
+            file_keys: BTreeSet::new(),
+            migration: None,
         }
     }
 }
 
 impl Toml {
     /// Creates a new `Toml` instance by reading the configuration from the specified file or from the default location.
-    pub fn new(file: Option<&Path>) -> Self {
+    /// Returns the problems found in the file, such as unknown keys, or fails if it can't be parsed.
+    pub fn new(file: Option<&Path>) -> anyhow::Result<(Self, Vec<String>)> {
         if let Some(file) = file {
-            return Self::read_custom_config(file);
+            return Self::read(file);
         }
 
-        let Some(config_dir) = dirs::config_local_dir() else {
+        let Some(file) = Self::default_file() else {
             warn!("Impossible to find config directory, using defaults");
-            return Toml::default();
+            return Ok((Toml::default(), Vec::new()));
         };
-
-        let app_name = env!("CARGO_PKG_NAME").to_string();
-        let app_dir = config_dir.join(&app_name);
-        let file = app_dir.join(format!("{app_name}.toml"));
+        let app_dir = file.parent().expect("Config file is in the app directory");
+        let config_dir = app_dir
+            .parent()
+            .expect("App directory is in the config directory");
 
         // Ensure the app directory exists before attempting migration
-        if let Err(e) = std::fs::create_dir_all(&app_dir) {
+        if let Err(e) = std::fs::create_dir_all(app_dir) {
             warn!(
                 "Failed to create config directory {}: {}",
                 app_dir.display(),
                 e
             );
-            return Toml::default();
+            return Ok((Toml::default(), Vec::new()));
         }
 
         // If the old config exists, migrate it to the new location
-        let old_file = config_dir.join(format!("{app_name}.toml"));
+        let old_file = config_dir.join(format!("{}.toml", env!("CARGO_PKG_NAME")));
         if !file.exists() && old_file.exists() {
             std::fs::rename(&old_file, &file).ok();
         }
 
         if file.exists() {
-            return Self::read_default_config(&file);
+            return Self::read(&file);
         }
 
         // Neither config exists: create default config in the new location
@@ -132,55 +329,205 @@ impl Toml {
             Err(e) => warn!("Failed to serialize default config: {}", e),
         }
 
-        default_config
+        Ok((default_config, Vec::new()))
     }
 
-    fn read_custom_config(file: &Path) -> Self {
-        let content = std::fs::read_to_string(file);
-        if let Ok(content) = content {
-            if let Ok(config) = toml::from_str(&content) {
-                return config;
-            }
-            warn!(
-                "Failed to parse config file {}, using defaults",
-                file.display()
-            );
-            return Toml::default();
+    /// Path of the configuration file in the default location.
+    fn default_file() -> Option<PathBuf> {
+        let app_name = env!("CARGO_PKG_NAME");
+        dirs::config_local_dir().map(|dir| dir.join(app_name).join(format!("{app_name}.toml")))
+    }
+
+    /// Reads and parses the configuration file, returning the unknown keys in it which are ignored.
+    /// The file of an older version is migrated to the current one in memory, leaving the file as it is.
+    fn read(file: &Path) -> anyhow::Result<(Self, Vec<String>)> {
+        let mut content = std::fs::read_to_string(file)
+            .with_context(|| format!("Failed to read config file {}", file.display()))?;
+
+        let mut document: toml_edit::DocumentMut = content
+            .parse()
+            .with_context(|| format!("Failed to parse config file {}", file.display()))?;
+        let migrated_from = migration::migrate(&mut document)
+            .with_context(|| format!("Failed to migrate config file {}", file.display()))?;
+        if migrated_from.is_some() {
+            content = document.to_string();
         }
-        warn!(
-            "Failed to read config file {}, using defaults",
-            file.display()
-        );
-        Toml::default()
+
+        let mut toml: Self = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse config file {}", file.display()))?;
+        let (keys, unknown_keys) = check::inspect_keys(&content, &toml);
+        toml.file_keys = keys;
+        toml.migration = migrated_from.map(|from| Migration {
+            from,
+            content: content.clone(),
+        });
+        let unknown_keys = unknown_keys
+            .into_iter()
+            .map(|key| format!("Unknown key {key} in config file {}", file.display()))
+            .collect();
+
+        Ok((toml, unknown_keys))
     }
 
-    fn read_default_config(file: &Path) -> Self {
-        let content = std::fs::read_to_string(file).unwrap_or_default();
-        if let Ok(config) = toml::from_str(&content) {
-            return config;
+    /// Returns the values which would be ignored or break the watcher, e.g. invalid filters.
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.poll_time.is_zero() {
+            problems.push("The poll time must be positive".to_string());
         }
-        warn!(
-            "Failed to parse config file {}, using defaults",
-            file.display()
-        );
-        Toml::default()
+        for (i, rule) in self.filters.iter().enumerate() {
+            if let Err(e) = Filter::new(rule) {
+                problems.push(format!("Ignoring the filter #{}: {e:#}", i + 1));
+            }
+        }
+        for (i, rule) in self.players.iter().enumerate() {
+            if let Err(e) = rule.validate() {
+                problems.push(format!("Ignoring the player #{}: {e:#}", i + 1));
+            }
+        }
+
+        problems
+    }
+
+    /// Returns the redactions which are invalid. The watcher doesn't start with them,
+    /// as the media they should hide would be reported unredacted.
+    fn validate_redactions(&self) -> Vec<String> {
+        self.redactions
+            .iter()
+            .enumerate()
+            .filter_map(|(i, rule)| {
+                Redaction::new(rule)
+                    .err()
+                    .map(|e| format!("Invalid redaction #{}: {e:#}", i + 1))
+            })
+            .collect()
     }
 }
 
+#[allow(clippy::struct_excessive_bools)]
 pub struct Config {
     pub host: String,
     pub port: u16,
     pub poll_interval: Duration,
+    pub bucket_id: String,
+    pub hostname: String,
+    pub bucket_per_player: bool,
+    pub report_mode: ReportMode,
     pub include_players: Vec<String>,
     pub exclude_players: Vec<String>,
+    pub include_sites: Vec<String>,
+    pub exclude_sites: Vec<String>,
+    pub mpris_signals: bool,
+    pub report_paused: bool,
+    pub extra_fields: Vec<ExtraField>,
+    pub ignore_muted: bool,
+    pub queue_size: usize,
+    /// Rules matching the fields of the media to include or exclude it.
+    pub filters: Vec<Filter>,
+    /// Rules hiding the content of the media before it's reported.
+    pub redactions: Vec<Redaction>,
+    /// Settings for the matching players, the first matching entry applies.
+    pub players: Vec<PlayerRule>,
+    /// Timeline of the mock media to play instead of the real players.
+    pub mock_timeline: Option<PathBuf>,
+    /// Whether the events are printed instead of sent.
+    pub dry_run: bool,
+    /// Whether the watcher refuses to start if the configuration has problems.
+    pub strict: bool,
+    /// Problems found in the configuration, such as unknown keys or invalid filters, which are ignored.
+    pub problems: Vec<String>,
+    /// Problems which the watcher refuses to start with even if it's not strict,
+    /// such as invalid redactions or a file which can't be parsed.
+    pub errors: Vec<String>,
+    /// Destinations of the reported media, the ActivityWatch server unless configured.
+    pub sinks: Vec<SinkConfig>,
+    /// Lowercase player name patterns with their friendly names, in the order they are matched.
+    pub aliases: Vec<(String, String)>,
+    /// The configuration file, which is reloaded when it changes.
+    pub file: Option<PathBuf>,
+    /// Command line options and environment variables, which still override the file when it's reloaded.
+    cli: Cli,
+    env: Env,
+    /// Migration of a file with an older version, which is saved once the watcher is sure to start.
+    migration: Option<Migration>,
 }
 
 impl Config {
     pub fn new(cli: Cli) -> Self {
-        let toml_data: Toml = Toml::new(cli.config.as_deref());
+        Self::load(cli).0
+    }
+
+    /// Replaces the file of an older version with its migration, keeping a backup.
+    pub fn save_migration(&self) {
+        if let (Some(migration), Some(file)) = (&self.migration, &self.file) {
+            migration.save(file);
+        }
+    }
+
+    /// Reads the configuration, returning the file settings too.
+    fn load(cli: Cli) -> (Self, Toml) {
+        let env = Env::read();
+        let file = cli.config.clone().or_else(|| env.config.clone());
+        let (toml_data, problems, error) = match Toml::new(file.as_deref()) {
+            Ok((toml_data, problems)) => (toml_data, problems, None),
+            Err(e) => (Toml::default(), Vec::new(), Some(e)),
+        };
+        let file = file.or_else(Toml::default_file);
+
+        let mut config = Self::merge(cli, env, toml_data.clone(), file, problems);
+        config.migration.clone_from(&toml_data.migration);
+        // The defaults would report the media which the file filters out or redacts.
+        if let Some(e) = error {
+            config.errors.push(format!("{e}: {:#}", e.root_cause()));
+        }
+
+        (config, toml_data)
+    }
+
+    /// Reads the configuration file again, keeping the command line options and the environment variables.
+    /// Fails if the file is missing or invalid, so that the current configuration can be kept.
+    /// Unknown keys are only logged, unless the configuration is strict.
+    pub fn reload(&self) -> anyhow::Result<Self> {
+        let Some(file) = &self.file else {
+            anyhow::bail!("There is no configuration file to reload");
+        };
+        let (toml_data, unknown_keys) = Toml::read(file)?;
+        let mut problems = toml_data.validate_redactions();
+        problems.extend(toml_data.validate());
+        if self.strict {
+            problems.extend(unknown_keys.iter().cloned());
+        }
+        if !problems.is_empty() {
+            anyhow::bail!(problems.join("\n"));
+        }
+        for key in &unknown_keys {
+            error!("{key}");
+        }
+
+        Ok(Self::merge(
+            self.cli.clone(),
+            self.env.clone(),
+            toml_data,
+            self.file.clone(),
+            unknown_keys,
+        ))
+    }
 
+    /// Merges the settings with the command line over the environment over the file over the defaults.
+    /// Lists from all of them are combined.
+    fn merge(
+        cli: Cli,
+        env: Env,
+        mut toml_data: Toml,
+        file: Option<PathBuf>,
+        mut problems: Vec<String>,
+    ) -> Self {
         trace!("TOML config: {:?}", toml_data);
+        trace!("Environment config: {:?}", env);
         trace!("CLI config: {:?}", cli);
+        env.apply(&mut toml_data);
+        problems.extend(env.problems.iter().cloned().chain(toml_data.validate()));
+        let errors = toml_data.validate_redactions();
 
         let mut include_players = vec![];
         include_players.extend(cli.include_players.iter().map(|s| s.to_lowercase()));
@@ -194,34 +541,213 @@ impl Config {
             exclude_players.clear();
         }
 
+        let mut include_sites = vec![];
+        include_sites.extend(cli.include_sites.iter().map(|s| s.to_lowercase()));
+        include_sites.extend(toml_data.include_sites.iter().map(|s| s.to_lowercase()));
+
+        let mut exclude_sites = vec![];
+        exclude_sites.extend(cli.exclude_sites.iter().map(|s| s.to_lowercase()));
+        exclude_sites.extend(toml_data.exclude_sites.iter().map(|s| s.to_lowercase()));
+        if !include_sites.is_empty() {
+            log::warn!("Include site filters specified, exclude site filters will be ignored");
+            exclude_sites.clear();
+        }
+
+        let mut extra_fields = cli.extra_fields.clone();
+        for field in toml_data.extra_fields {
+            if !extra_fields.contains(&field) {
+                extra_fields.push(field);
+            }
+        }
+
+        let filters = toml_data
+            .filters
+            .iter()
+            .filter_map(|rule| Filter::new(rule).ok())
+            .collect();
+
+        let redactions = toml_data
+            .redactions
+            .iter()
+            .filter_map(|rule| Redaction::new(rule).ok())
+            .collect();
+
+        let players = toml_data
+            .players
+            .into_iter()
+            .filter(|rule| rule.validate().is_ok())
+            .collect();
+
+        let mut sinks = toml_data.sinks;
+        if sinks.is_empty() {
+            sinks.push(SinkConfig::ActivityWatch);
+        }
+
+        // Longer patterns are more specific, so they are matched first.
+        let mut aliases: Vec<(String, String)> = toml_data
+            .aliases
+            .into_iter()
+            .map(|(pattern, alias)| (pattern.to_lowercase(), alias))
+            .collect();
+        aliases.sort_by_key(|(pattern, _)| std::cmp::Reverse(pattern.len()));
+        aliases.extend(
+            BUILTIN_ALIASES
+                .iter()
+                .map(|(pattern, alias)| (pattern.to_lowercase(), (*alias).to_string())),
+        );
+
         Config {
-            host: cli.host.unwrap_or(toml_data.host),
+            host: cli.host.clone().unwrap_or(toml_data.host),
             port: cli.port.unwrap_or(toml_data.port),
-            poll_interval: Duration::from_secs(cli.poll_interval.unwrap_or(toml_data.poll_time)),
+            poll_interval: cli
+                .poll_interval
+                .map_or(toml_data.poll_time, Duration::from_secs),
+            bucket_id: cli.bucket_id.clone().unwrap_or(toml_data.bucket_id),
+            hostname: cli
+                .hostname
+                .clone()
+                .or(toml_data.hostname)
+                .unwrap_or_else(|| gethostname::gethostname().to_string_lossy().into_owned()),
+            bucket_per_player: cli.bucket_per_player.unwrap_or(toml_data.bucket_per_player),
+            report_mode: cli.report_mode.unwrap_or(toml_data.report_mode),
             include_players,
             exclude_players,
+            include_sites,
+            exclude_sites,
+            mpris_signals: cli.mpris_signals.unwrap_or(toml_data.mpris_signals),
+            report_paused: cli.report_paused.unwrap_or(toml_data.report_paused),
+            extra_fields,
+            ignore_muted: cli.ignore_muted.unwrap_or(toml_data.ignore_muted),
+            queue_size: cli.queue_size.unwrap_or(toml_data.queue_size),
+            filters,
+            redactions,
+            players,
+            mock_timeline: cli.mock_timeline.clone(),
+            dry_run: cli.dry_run,
+            strict: cli.strict,
+            problems,
+            errors,
+            sinks,
+            aliases,
+            file,
+            cli,
+            env,
+            migration: None,
         }
     }
 
-    pub fn report_player(&self, player: &str) -> bool {
+    /// Whether heartbeats are kept while the server is unreachable.
+    pub fn is_queued(&self) -> bool {
+        self.queue_size > 0
+    }
+
+    /// Returns the friendly name of the player if any alias matches it.
+    pub fn player_alias(&self, player: &str) -> Option<&str> {
+        let player = player.to_lowercase();
+        self.aliases
+            .iter()
+            .find(|(pattern, _)| player.contains(pattern.as_str()))
+            .map(|(_, alias)| alias.as_str())
+    }
+
+    /// Resolves how the player's media is reported, from the first `[[players]]` entry
+    /// matching its name or alias, and the global settings.
+    pub fn report_player(&self, data: &MediaData) -> PlayerPolicy {
+        let alias = self.player_alias(&data.player);
+        let rule = self
+            .players
+            .iter()
+            .find(|rule| rule.matches(&data.player, alias));
+        let rule_alias = rule.and_then(|rule| rule.alias.as_deref());
+        let names: Vec<&str> = [Some(data.player.as_str()), alias, rule_alias]
+            .into_iter()
+            .flatten()
+            .collect();
+
+        PlayerPolicy {
+            player: rule_alias.or(alias).unwrap_or(&data.player).to_string(),
+            included: self.is_player_included(&names),
+            report_paused: rule
+                .and_then(|rule| rule.report_paused)
+                .unwrap_or(self.report_paused),
+            min_duration: rule.map_or(Duration::ZERO, |rule| rule.min_duration),
+            extra_fields: rule
+                .and_then(|rule| rule.extra_fields.clone())
+                .unwrap_or_else(|| self.extra_fields.clone()),
+            bucket_id: rule.and_then(|rule| rule.bucket_id.clone()),
+        }
+    }
+
+    /// Whether the player passes the include and exclude lists by any of its names,
+    /// its own name or its aliases, so that the lists written for either of them keep working.
+    fn is_player_included(&self, names: &[&str]) -> bool {
+        let names: Vec<String> = names.iter().map(|name| name.to_lowercase()).collect();
+        let matches = |filter: &String| names.iter().any(|name| name.contains(filter.as_str()));
+
         if !self.include_players.is_empty() {
-            for filter in &self.include_players {
-                if player.to_lowercase().contains(filter) {
-                    return true;
-                }
-            }
-            return false;
+            return self.include_players.iter().any(matches);
         }
-        if !self.exclude_players.is_empty() {
-            for filter in &self.exclude_players {
-                if player.to_lowercase().contains(filter) {
-                    return false;
-                }
-            }
+
+        !self.exclude_players.iter().any(matches)
+    }
+
+    /// Whether the media from the website should be reported, `None` for media not from a website.
+    pub fn report_site(&self, site: Option<&str>) -> bool {
+        let matches = |site: &str, filter: &String| {
+            site == filter
+                || site
+                    .strip_suffix(filter.as_str())
+                    .is_some_and(|subdomain| subdomain.ends_with('.'))
+        };
+
+        if !self.include_sites.is_empty() {
+            let Some(site) = site.map(str::to_lowercase) else {
+                return false;
+            };
+            return self
+                .include_sites
+                .iter()
+                .any(|filter| matches(&site, filter));
+        }
+        if let Some(site) = site.map(str::to_lowercase) {
+            return !self
+                .exclude_sites
+                .iter()
+                .any(|filter| matches(&site, filter));
         }
 
         true
     }
+
+    /// Returns why the media is not reported by the player's policy, or `None` if it should be reported.
+    pub fn filter_reason(&self, data: &MediaData, policy: &PlayerPolicy) -> Option<String> {
+        if data.status == PlaybackStatus::Paused && !policy.report_paused {
+            return Some("paused media is not reported".to_string());
+        }
+        if self.ignore_muted && data.is_muted() {
+            return Some("the player is muted".to_string());
+        }
+        if !self.report_site(data.site()) {
+            return Some(match data.site() {
+                Some(site) => format!("site \"{site}\" is filtered out"),
+                None => "only media from the included sites is reported".to_string(),
+            });
+        }
+        if !policy.included {
+            return Some(if self.include_players.is_empty() {
+                "the player is excluded".to_string()
+            } else {
+                "the player is not included".to_string()
+            });
+        }
+        if !self.filters.is_empty() {
+            // Any field may be filtered on, even if it's not reported.
+            let serialized = data.serialize(ExtraField::value_variants(), &[]);
+            return filter::filter_reason(&self.filters, &serialized);
+        }
+
+        None
+    }
 }
 
 #[cfg(test)]
@@ -232,22 +758,33 @@ mod tests {
 
     use tempfile::tempdir;
 
+    use super::filter::FilterAction;
+    use crate::sink::{FileFormat, FileSinkConfig};
+
     static ENV_LOCK: Mutex<()> = Mutex::new(());
 
     struct EnvGuard {
-        key: &'static str,
-        previous: Option<std::ffi::OsString>,
+        previous: Vec<(&'static str, Option<std::ffi::OsString>)>,
         _env_lock: std::sync::MutexGuard<'static, ()>,
     }
 
     impl EnvGuard {
         fn set(key: &'static str, value: &std::ffi::OsStr) -> Self {
+            Self::set_all(&[(key, value)])
+        }
+
+        fn set_all(vars: &[(&'static str, &std::ffi::OsStr)]) -> Self {
             let env_lock = ENV_LOCK.lock().unwrap();
-            let previous = std::env::var_os(key);
-            // Safe because tests serialize environment mutation with ENV_LOCK.
-            std::env::set_var(key, value);
+            let previous = vars
+                .iter()
+                .map(|(key, value)| {
+                    let previous = std::env::var_os(key);
+                    // Safe because tests serialize environment mutation with ENV_LOCK.
+                    std::env::set_var(key, value);
+                    (*key, previous)
+                })
+                .collect();
             Self {
-                key,
                 previous,
                 _env_lock: env_lock,
             }
@@ -257,28 +794,139 @@ mod tests {
     impl Drop for EnvGuard {
         fn drop(&mut self) {
             // Safe because tests serialize environment mutation with ENV_LOCK.
-            match &self.previous {
-                Some(val) => std::env::set_var(self.key, val),
-                None => std::env::remove_var(self.key),
+            for (key, previous) in self.previous.iter().rev() {
+                match previous {
+                    Some(val) => std::env::set_var(key, val),
+                    None => std::env::remove_var(key),
+                }
             }
         }
     }
 
     const SAMPLE_CONFIG: &str = r#"
+version = 2
 port = 1234
 host = "example.com"
-poll_time = 42
+poll_time = "42s"
+bucket_id = "media"
+hostname = "desktop"
+bucket_per_player = true
+report_mode = "track"
 include_players = ["VLC", "Spotify"]
 exclude_players = ["Firefox"]
+include_sites = ["youtube.com"]
+exclude_sites = ["example.com"]
+mpris_signals = false
+report_paused = true
+extra_fields = ["genre", "track_number"]
+ignore_muted = true
+queue_size = 100
+
+[[filters]]
+action = "exclude"
+player = "(?i)firefox"
+title = "(?i)standup|meeting"
+
+[[redactions]]
+fields = ["title", "uri"]
+salt = "pepper"
+
+[redactions.match]
+site = "^internal\\.example\\.com$"
+
+[[players]]
+player = "nightly"
+alias = "Firefox Nightly"
+report_paused = false
+min_duration = "30s"
+extra_fields = []
+bucket_id = "browser"
+
+[[players]]
+player = "Spotify"
+min_duration = "10s"
+
+[[sinks]]
+type = "activitywatch"
+
+[[sinks]]
+type = "file"
+path = "listening.csv"
+format = "csv"
+
+[aliases]
+"Mozilla" = "Browser"
+"Mozilla Firefox Nightly" = "Nightly"
 "#;
 
     fn sample_toml() -> Toml {
         Toml {
+            version: 2,
             port: 1234,
             host: "example.com".to_string(),
-            poll_time: 42,
+            poll_time: Duration::from_secs(42),
+            bucket_id: "media".to_string(),
+            hostname: Some("desktop".to_string()),
+            bucket_per_player: true,
+            report_mode: ReportMode::Track,
             include_players: vec!["VLC".to_string(), "Spotify".to_string()],
             exclude_players: vec!["Firefox".to_string()],
+            include_sites: vec!["youtube.com".to_string()],
+            exclude_sites: vec!["example.com".to_string()],
+            mpris_signals: false,
+            report_paused: true,
+            extra_fields: vec![ExtraField::Genre, ExtraField::TrackNumber],
+            ignore_muted: true,
+            queue_size: 100,
+            filters: vec![FilterRule {
+                action: FilterAction::Exclude,
+                fields: BTreeMap::from([
+                    ("player".to_string(), "(?i)firefox".to_string()),
+                    ("title".to_string(), "(?i)standup|meeting".to_string()),
+                ]),
+            }],
+            redactions: vec![RedactionRule {
+                matches: BTreeMap::from([(
+                    "site".to_string(),
+                    r"^internal\.example\.com$".to_string(),
+                )]),
+                fields: vec!["title".to_string(), "uri".to_string()],
+                placeholder: "[redacted]".to_string(),
+                salt: Some("pepper".to_string()),
+            }],
+            players: vec![
+                PlayerRule {
+                    player: "nightly".to_string(),
+                    alias: Some("Firefox Nightly".to_string()),
+                    report_paused: Some(false),
+                    min_duration: Duration::from_secs(30),
+                    extra_fields: Some(vec![]),
+                    bucket_id: Some("browser".to_string()),
+                },
+                PlayerRule {
+                    player: "Spotify".to_string(),
+                    alias: None,
+                    report_paused: None,
+                    min_duration: Duration::from_secs(10),
+                    extra_fields: None,
+                    bucket_id: None,
+                },
+            ],
+            sinks: vec![
+                SinkConfig::ActivityWatch,
+                SinkConfig::File(FileSinkConfig {
+                    path: PathBuf::from("listening.csv"),
+                    format: FileFormat::Csv,
+                    max_size: 10 * 1024 * 1024,
+                    max_files: 5,
+                }),
+            ],
+            aliases: BTreeMap::from([
+                ("Mozilla".to_string(), "Browser".to_string()),
+                ("Mozilla Firefox Nightly".to_string(), "Nightly".to_string()),
+            ]),
+            file_keys: BTreeSet::new(),
+            migration: None,
         }
     }
 
@@ -293,11 +941,28 @@ exclude_players = ["Firefox"]
     }
 
     fn assert_toml_eq(actual: &Toml, expected: &Toml) {
+        assert_eq!(actual.version, expected.version);
         assert_eq!(actual.port, expected.port);
         assert_eq!(actual.host, expected.host);
         assert_eq!(actual.poll_time, expected.poll_time);
+        assert_eq!(actual.bucket_id, expected.bucket_id);
+        assert_eq!(actual.hostname, expected.hostname);
+        assert_eq!(actual.bucket_per_player, expected.bucket_per_player);
+        assert_eq!(actual.report_mode, expected.report_mode);
         assert_eq!(actual.include_players, expected.include_players);
         assert_eq!(actual.exclude_players, expected.exclude_players);
+        assert_eq!(actual.include_sites, expected.include_sites);
+        assert_eq!(actual.exclude_sites, expected.exclude_sites);
+        assert_eq!(actual.mpris_signals, expected.mpris_signals);
+        assert_eq!(actual.report_paused, expected.report_paused);
+        assert_eq!(actual.extra_fields, expected.extra_fields);
+        assert_eq!(actual.ignore_muted, expected.ignore_muted);
+        assert_eq!(actual.queue_size, expected.queue_size);
+        assert_eq!(actual.filters, expected.filters);
+        assert_eq!(actual.redactions, expected.redactions);
+        assert_eq!(actual.players, expected.players);
+        assert_eq!(actual.sinks, expected.sinks);
+        assert_eq!(actual.aliases, expected.aliases);
     }
 
     #[test]
@@ -306,7 +971,8 @@ exclude_players = ["Firefox"]
         let config_file = temp_dir.path().join("custom-config.toml");
         std::fs::write(&config_file, SAMPLE_CONFIG).unwrap();
 
-        let config = Toml::new(Some(&config_file));
+        let (config, problems) = Toml::new(Some(&config_file)).unwrap();
+        assert!(problems.is_empty(), "{problems:?}");
 
         assert_toml_eq(&config, &sample_toml());
     }
@@ -320,7 +986,8 @@ exclude_players = ["Firefox"]
         std::fs::create_dir_all(config_file.parent().unwrap()).unwrap();
         std::fs::write(&config_file, SAMPLE_CONFIG).unwrap();
 
-        let config = Toml::new(None);
+        let (config, problems) = Toml::new(None).unwrap();
+        assert!(problems.is_empty(), "{problems:?}");
 
         assert_toml_eq(&config, &sample_toml());
     }
@@ -333,7 +1000,8 @@ exclude_players = ["Firefox"]
         let config_file = default_config_file(temp_dir.path());
         log::warn!("Default config file path: {}", config_file.display());
 
-        let config = Toml::new(None);
+        let (config, problems) = Toml::new(None).unwrap();
+        assert!(problems.is_empty(), "{problems:?}");
         let expected = Toml::default();
         assert_toml_eq(&config, &expected);
         assert!(config_file.exists());
@@ -352,7 +1020,8 @@ exclude_players = ["Firefox"]
         std::fs::write(&legacy_file, SAMPLE_CONFIG).unwrap();
         assert!(legacy_file.exists());
 
-        let config = Toml::new(None);
+        let (config, problems) = Toml::new(None).unwrap();
+        assert!(problems.is_empty(), "{problems:?}");
 
         assert_toml_eq(&config, &sample_toml());
 
@@ -365,6 +1034,40 @@ exclude_players = ["Firefox"]
         assert_eq!(SAMPLE_CONFIG, std::fs::read_to_string(&new_file).unwrap());
     }
 
+    #[test]
+    fn migrates_old_config_version_with_backup() {
+        let temp_dir = tempdir().unwrap();
+        let config_file = temp_dir.path().join("config.toml");
+        let legacy = "# Polling\npoll_time = 10\n\n[aliases]\n\"Mozilla\" = \"Browser\"\n";
+        std::fs::write(&config_file, legacy).unwrap();
+
+        let (config, problems) = Toml::new(Some(&config_file)).unwrap();
+        assert!(problems.is_empty(), "{problems:?}");
+        assert_eq!(config.version, migration::CURRENT_VERSION);
+        assert_eq!(config.poll_time, Duration::from_secs(10));
+        assert_eq!(config.migration.as_ref().unwrap().from, 1);
+        // Reading the file, e.g. to check or reload it, doesn't rewrite it.
+        let backup = temp_dir.path().join("config.toml.v1.bak");
+        assert_eq!(std::fs::read_to_string(&config_file).unwrap(), legacy);
+        assert!(!backup.exists());
+
+        let config_path = config_file.to_str().unwrap();
+        let cli = Cli::parse_from(["aw-watcher-media-player", "--config", config_path]);
+        Config::new(cli.clone());
+        assert_eq!(std::fs::read_to_string(&config_file).unwrap(), legacy);
+        Config::new(cli).save_migration();
+        assert_eq!(std::fs::read_to_string(backup).unwrap(), legacy);
+        let migrated = std::fs::read_to_string(&config_file).unwrap();
+        assert!(migrated.starts_with("# Polling\npoll_time = \"10s\"\nversion = 2\n"));
+        assert_toml_eq(&toml::from_str(&migrated).unwrap(), &config);
+        let (config, _) = Toml::new(Some(&config_file)).unwrap();
+        assert!(config.migration.is_none());
+
+        std::fs::write(&config_file, "version = 99").unwrap();
+        let error = Toml::new(Some(&config_file)).unwrap_err();
+        assert!(format!("{error:#}").contains("newer"), "{error:#}");
+    }
+
     #[test]
     fn config_new_applies_cli_over_toml_and_defaults() {
         let temp_dir = tempdir().unwrap();
@@ -378,9 +1081,24 @@ exclude_players = ["Firefox"]
             host: Some("cli-host".to_string()),
             port: Some(9999),
             poll_interval: Some(10),
+            bucket_id: None,
+            hostname: Some("laptop".to_string()),
+            bucket_per_player: None,
+            report_mode: None,
             include_players: vec!["CliPlayer".to_string()],
             exclude_players: vec!["CliExclude".to_string()],
+            include_sites: vec![],
+            exclude_sites: vec![],
+            mpris_signals: Some(true),
+            report_paused: None,
+            extra_fields: vec![ExtraField::ArtUrl, ExtraField::Genre],
+            ignore_muted: Some(false),
+            queue_size: Some(0),
+            mock_timeline: None,
+            dry_run: false,
+            strict: false,
             verbosity: Verbosity::new(0, 1),
+            command: None,
         };
 
         let config = Config::new(cli);
@@ -388,6 +1106,25 @@ exclude_players = ["Firefox"]
         assert_eq!(config.host, "cli-host");
         assert_eq!(config.port, 9999);
         assert_eq!(config.poll_interval, Duration::from_secs(10));
+        assert_eq!(config.bucket_id, "media");
+        assert_eq!(config.hostname, "laptop");
+        assert!(config.bucket_per_player);
+        assert_eq!(config.report_mode, ReportMode::Track);
+        assert!(config.mpris_signals);
+        assert!(config.report_paused);
+        assert!(!config.ignore_muted);
+        assert!(!config.is_queued());
+        assert_eq!(config.filters.len(), 1);
+        assert_eq!(config.redactions.len(), 1);
+        assert_eq!(config.sinks.len(), 2);
+        assert_eq!(
+            config.extra_fields,
+            vec![
+                ExtraField::ArtUrl,
+                ExtraField::Genre,
+                ExtraField::TrackNumber
+            ]
+        );
         assert_eq!(
             config.include_players,
             vec![
@@ -402,6 +1139,188 @@ exclude_players = ["Firefox"]
         );
     }
 
+    #[test]
+    fn config_new_applies_cli_over_env_over_toml() {
+        let temp_dir = tempdir().unwrap();
+        let config_file = temp_dir.path().join("config.toml");
+        std::fs::write(&config_file, SAMPLE_CONFIG).unwrap();
+        let _guard = EnvGuard::set_all(&[
+            ("XDG_CONFIG_HOME", temp_dir.path().as_os_str()),
+            ("AW_MEDIA_CONFIG", config_file.as_os_str()),
+            ("AW_MEDIA_HOST", "env-host".as_ref()),
+            ("AW_MEDIA_PORT", "7777".as_ref()),
+            ("AW_MEDIA_POLL_INTERVAL", "3".as_ref()),
+            ("AW_MEDIA_EXCLUDE_PLAYERS", "EnvExclude".as_ref()),
+            ("AW_MEDIA_REPORT_MODE", "heartbeat".as_ref()),
+            ("AW_MEDIA_QUEUE_SIZE", "invalid".as_ref()),
+        ]);
+
+        let cli = Cli {
+            config: None,
+            host: None,
+            port: Some(9999),
+            poll_interval: None,
+            bucket_id: None,
+            hostname: None,
+            bucket_per_player: None,
+            report_mode: None,
+            include_players: vec![],
+            exclude_players: vec!["CliExclude".to_string()],
+            include_sites: vec![],
+            exclude_sites: vec![],
+            mpris_signals: None,
+            report_paused: None,
+            extra_fields: vec![],
+            ignore_muted: None,
+            queue_size: None,
+            mock_timeline: None,
+            dry_run: false,
+            strict: false,
+            verbosity: Verbosity::new(0, 1),
+            command: None,
+        };
+
+        let config = Config::new(cli);
+
+        assert_eq!(config.file.as_deref(), Some(config_file.as_path()));
+        assert_eq!(config.host, "env-host");
+        assert_eq!(config.port, 9999);
+        assert_eq!(config.poll_interval, Duration::from_secs(3));
+        assert_eq!(config.report_mode, ReportMode::Heartbeat);
+        assert_eq!(config.bucket_id, "media");
+        assert_eq!(config.queue_size, 100);
+        // Include players from the file make the exclude filters ignored.
+        assert_eq!(config.include_players, vec!["vlc", "spotify"]);
+        assert!(config.exclude_players.is_empty());
+
+        std::fs::write(&config_file, "exclude_players = [\"Firefox\"]").unwrap();
+        let reloaded = config.reload().unwrap();
+        assert_eq!(reloaded.host, "env-host");
+        assert_eq!(
+            reloaded.exclude_players,
+            vec!["cliexclude", "envexclude", "firefox"]
+        );
+    }
+
+    #[test]
+    fn reload_keeps_cli_options_and_rejects_invalid_file() {
+        let temp_dir = tempdir().unwrap();
+        let _guard = EnvGuard::set("XDG_CONFIG_HOME", temp_dir.path().as_os_str());
+
+        let config_file = temp_dir.path().join("config.toml");
+        std::fs::write(&config_file, SAMPLE_CONFIG).unwrap();
+
+        let cli = Cli {
+            config: Some(config_file.clone()),
+            host: None,
+            port: Some(9999),
+            poll_interval: None,
+            bucket_id: None,
+            hostname: None,
+            bucket_per_player: None,
+            report_mode: None,
+            include_players: vec![],
+            exclude_players: vec![],
+            include_sites: vec![],
+            exclude_sites: vec![],
+            mpris_signals: None,
+            report_paused: None,
+            extra_fields: vec![],
+            ignore_muted: None,
+            queue_size: None,
+            mock_timeline: None,
+            dry_run: false,
+            strict: false,
+            verbosity: Verbosity::new(0, 1),
+            command: None,
+        };
+        let config = Config::new(cli);
+        assert_eq!(config.file.as_deref(), Some(config_file.as_path()));
+        assert_eq!(config.poll_interval, Duration::from_secs(42));
+
+        std::fs::write(
+            &config_file,
+            "port = 1\nhost = \"example.org\"\npoll_time = 3",
+        )
+        .unwrap();
+        let reloaded = config.reload().unwrap();
+        assert_eq!(reloaded.port, 9999);
+        assert_eq!(reloaded.host, "example.org");
+        assert_eq!(reloaded.poll_interval, Duration::from_secs(3));
+        assert!(reloaded.filters.is_empty());
+
+        for invalid in [
+            "poll_time = \"5\"",
+            "poll_time = 0",
+            "[[filters]]\naction = \"exclude\"\ntitle = \"(\"",
+        ] {
+            std::fs::write(&config_file, invalid).unwrap();
+            assert!(reloaded.reload().is_err(), "{invalid} should be rejected");
+        }
+        std::fs::remove_file(&config_file).unwrap();
+        assert!(reloaded.reload().is_err());
+    }
+
+    #[test]
+    fn config_collects_problems_and_strict_reload_rejects_unknown_keys() {
+        let temp_dir = tempdir().unwrap();
+        let _guard = EnvGuard::set("XDG_CONFIG_HOME", temp_dir.path().as_os_str());
+
+        let config_file = temp_dir.path().join("config.toml");
+        std::fs::write(
+            &config_file,
+            "exclude_player = [\"Firefox\"]\n\n[[filters]]\naction = \"exclude\"\ntitle = \"(\"\n\n\
+             [[players]]\nplayer = \" \"\n\n[[redactions]]\nfields = [\"titel\"]\n",
+        )
+        .unwrap();
+
+        let mut cli = Cli::parse_from([
+            "aw-watcher-media-player".as_ref(),
+            "--config".as_ref(),
+            config_file.as_os_str(),
+        ]);
+        let config = Config::new(cli.clone());
+        assert!(config.filters.is_empty());
+        assert!(config.players.is_empty());
+        assert_eq!(config.problems.len(), 3);
+        assert!(config.problems[0]
+            .starts_with("Unknown key \"exclude_player\" at line 1, column 1 in config file"));
+        assert!(config.problems[1].starts_with("Ignoring the filter #1: Invalid regex for title"));
+        assert_eq!(
+            config.problems[2],
+            "Ignoring the player #1: The player to match is empty"
+        );
+        assert_eq!(
+            config.errors,
+            ["Invalid redaction #1: Unknown field \"titel\" to redact"]
+        );
+
+        std::fs::write(&config_file, "exclude_player = [\"Firefox\"]").unwrap();
+        let reloaded = config.reload().unwrap();
+        assert_eq!(reloaded.problems.len(), 1);
+        assert!(reloaded.errors.is_empty());
+        std::fs::write(&config_file, "[[redactions]]\nfields = [\"titel\"]").unwrap();
+        assert!(reloaded.reload().is_err());
+
+        cli.strict = true;
+        let strict = Config::new(cli);
+        assert!(strict.reload().is_err());
+
+        std::fs::write(&config_file, "port = ").unwrap();
+        let config = Config::new(Cli::parse_from([
+            "aw-watcher-media-player".as_ref(),
+            "--config".as_ref(),
+            config_file.as_os_str(),
+        ]));
+        assert_eq!(config.port, default_port());
+        assert_eq!(config.errors.len(), 1);
+        assert!(
+            config.errors[0].contains("line 1, column 8"),
+            "{:?}",
+            config.errors
+        );
+    }
+
     #[test]
     fn report_player_filters_by_include() {
         let temp_dir = tempdir().unwrap();
@@ -412,30 +1331,60 @@ exclude_players = ["Firefox"]
             host: None,
             port: None,
             poll_interval: None,
+            bucket_id: None,
+            hostname: None,
+            bucket_per_player: None,
+            report_mode: None,
             include_players: vec!["Spotify".to_string(), "Firefox".to_string()],
             exclude_players: vec!["firefox".to_string()],
+            include_sites: vec![],
+            exclude_sites: vec![],
+            mpris_signals: None,
+            report_paused: None,
+            extra_fields: vec![],
+            ignore_muted: None,
+            queue_size: None,
+            mock_timeline: None,
+            dry_run: false,
+            strict: false,
             verbosity: Verbosity::new(0, 1),
+            command: None,
         };
 
         let config = Config::new(cli);
 
-        assert!(config.report_player("Spotify"));
-        assert!(config.report_player("SPOTIFY-CONNECT"));
-        assert!(config.report_player("Firefox"));
-        assert!(config.report_player("firefox-tab"));
-        assert!(!config.report_player("VLC"));
+        assert!(config.is_player_included(&["Spotify"]));
+        assert!(config.is_player_included(&["SPOTIFY-CONNECT"]));
+        assert!(config.is_player_included(&["Firefox"]));
+        assert!(config.is_player_included(&["firefox-tab"]));
+        assert!(!config.is_player_included(&["VLC"]));
 
         let cli_all = Cli {
             config: None,
             host: None,
             port: None,
             poll_interval: None,
+            bucket_id: None,
+            hostname: None,
+            bucket_per_player: None,
+            report_mode: None,
             include_players: vec![],
             exclude_players: vec![],
+            include_sites: vec![],
+            exclude_sites: vec![],
+            mpris_signals: None,
+            report_paused: None,
+            extra_fields: vec![],
+            ignore_muted: None,
+            queue_size: None,
+            mock_timeline: None,
+            dry_run: false,
+            strict: false,
             verbosity: Verbosity::new(0, 1),
+            command: None,
         };
         let config_all = Config::new(cli_all);
-        assert!(config_all.report_player("Anything"));
+        assert!(config_all.is_player_included(&["Anything"]));
     }
 
     #[test]
@@ -448,21 +1397,314 @@ exclude_players = ["Firefox"]
             host: None,
             port: None,
             poll_interval: None,
+            bucket_id: None,
+            hostname: None,
+            bucket_per_player: None,
+            report_mode: None,
             include_players: vec![],
             exclude_players: vec!["Firefox".to_string(), "VLC".to_string()],
+            include_sites: vec![],
+            exclude_sites: vec![],
+            mpris_signals: None,
+            report_paused: None,
+            extra_fields: vec![],
+            ignore_muted: None,
+            queue_size: None,
+            mock_timeline: None,
+            dry_run: false,
+            strict: false,
             verbosity: Verbosity::new(0, 1),
+            command: None,
         };
 
         let config = Config::new(cli);
 
         // Excluded players
-        assert!(!config.report_player("Firefox"));
-        assert!(!config.report_player("firefox-tab"));
-        assert!(!config.report_player("VLC"));
-        assert!(!config.report_player("vlc-media-player"));
+        assert!(!config.is_player_included(&["Firefox"]));
+        assert!(!config.is_player_included(&["firefox-tab"]));
+        assert!(!config.is_player_included(&["VLC"]));
+        assert!(!config.is_player_included(&["vlc-media-player"]));
 
         // Non-excluded players
-        assert!(config.report_player("Spotify"));
-        assert!(config.report_player("Spotify-Connect"));
+        assert!(config.is_player_included(&["Spotify"]));
+        assert!(config.is_player_included(&["Spotify-Connect"]));
+    }
+
+    #[test]
+    fn player_filters_match_name_and_alias() {
+        let temp_dir = tempdir().unwrap();
+        let _guard = EnvGuard::set("XDG_CONFIG_HOME", temp_dir.path().as_os_str());
+        let config = |args: &[&str]| {
+            Config::new(Cli::parse_from(
+                ["aw-watcher-media-player"].iter().chain(args),
+            ))
+        };
+        let included = |config: &Config, player: &str| {
+            let mut data = MediaData::default();
+            data.player = player.to_string();
+            config.report_player(&data).included
+        };
+
+        // The built-in aliases rename "Mozilla Firefox" to "Firefox" and "MSEdge" to "Microsoft Edge".
+        let exclude = config(&["--exclude-players", "mozilla,edge"]);
+        assert!(!included(&exclude, "Mozilla Firefox"));
+        assert!(!included(&exclude, "MSEdge"));
+        assert!(included(&exclude, "Spotify"));
+
+        let include = config(&["--include-players", "MSEdge,ZuneMusic"]);
+        assert!(included(&include, "MSEdge"));
+        assert!(included(
+            &include,
+            "Microsoft.ZuneMusic_8wekyb3d8bbwe!Microsoft.ZuneMusic"
+        ));
+        assert!(!included(&include, "Mozilla Firefox"));
+    }
+
+    #[test]
+    fn filter_reason_explains_filtered_media() {
+        let temp_dir = tempdir().unwrap();
+        let _guard = EnvGuard::set("XDG_CONFIG_HOME", temp_dir.path().as_os_str());
+
+        let cli = Cli {
+            config: None,
+            host: None,
+            port: None,
+            poll_interval: None,
+            bucket_id: None,
+            hostname: None,
+            bucket_per_player: None,
+            report_mode: None,
+            include_players: vec![],
+            exclude_players: vec!["Firefox".to_string()],
+            include_sites: vec![],
+            exclude_sites: vec![],
+            mpris_signals: None,
+            report_paused: None,
+            extra_fields: vec![],
+            ignore_muted: None,
+            queue_size: None,
+            mock_timeline: None,
+            dry_run: true,
+            strict: false,
+            verbosity: Verbosity::new(0, 1),
+            command: None,
+        };
+
+        let config = Config::new(cli);
+        assert!(config.dry_run);
+        let filter_reason =
+            |data: &MediaData| config.filter_reason(data, &config.report_player(data));
+
+        let mut data = MediaData::default();
+        data.player = "Spotify".to_string();
+        assert_eq!(filter_reason(&data), None);
+
+        data.status = PlaybackStatus::Paused;
+        assert_eq!(
+            filter_reason(&data).as_deref(),
+            Some("paused media is not reported")
+        );
+
+        data.status = PlaybackStatus::Playing;
+        data.player = "Firefox".to_string();
+        assert_eq!(
+            filter_reason(&data).as_deref(),
+            Some("the player is excluded")
+        );
+    }
+
+    #[test]
+    fn player_alias_prefers_specific_and_user_aliases() {
+        let temp_dir = tempdir().unwrap();
+        let _guard = EnvGuard::set("XDG_CONFIG_HOME", temp_dir.path().as_os_str());
+
+        let config_file = temp_dir.path().join("config.toml");
+        std::fs::write(&config_file, SAMPLE_CONFIG).unwrap();
+
+        let cli = Cli {
+            config: Some(config_file),
+            host: None,
+            port: None,
+            poll_interval: None,
+            bucket_id: None,
+            hostname: None,
+            bucket_per_player: None,
+            report_mode: None,
+            include_players: vec![],
+            exclude_players: vec![],
+            include_sites: vec![],
+            exclude_sites: vec![],
+            mpris_signals: None,
+            report_paused: None,
+            extra_fields: vec![],
+            ignore_muted: None,
+            queue_size: None,
+            mock_timeline: None,
+            dry_run: false,
+            strict: false,
+            verbosity: Verbosity::new(0, 1),
+            command: None,
+        };
+
+        let config = Config::new(cli);
+
+        assert_eq!(config.player_alias("Mozilla Firefox"), Some("Browser"));
+        assert_eq!(
+            config.player_alias("mozilla firefox nightly"),
+            Some("Nightly")
+        );
+        assert_eq!(
+            config.player_alias("Microsoft.ZuneMusic_8wekyb3d8bbwe!Microsoft.ZuneMusic"),
+            Some("Media Player")
+        );
+        assert_eq!(config.player_alias("VLC"), None);
+    }
+
+    #[test]
+    fn report_player_resolves_first_matching_player_rule() {
+        let temp_dir = tempdir().unwrap();
+        let _guard = EnvGuard::set("XDG_CONFIG_HOME", temp_dir.path().as_os_str());
+
+        let config_file = temp_dir.path().join("config.toml");
+        std::fs::write(&config_file, SAMPLE_CONFIG).unwrap();
+
+        let cli = Cli {
+            config: Some(config_file),
+            host: None,
+            port: None,
+            poll_interval: None,
+            bucket_id: None,
+            hostname: None,
+            bucket_per_player: None,
+            report_mode: None,
+            include_players: vec!["Firefox".to_string()],
+            exclude_players: vec![],
+            include_sites: vec![],
+            exclude_sites: vec![],
+            mpris_signals: None,
+            report_paused: None,
+            extra_fields: vec![ExtraField::Composer],
+            ignore_muted: None,
+            queue_size: None,
+            mock_timeline: None,
+            dry_run: false,
+            strict: false,
+            verbosity: Verbosity::new(0, 1),
+            command: None,
+        };
+
+        let config = Config::new(cli);
+        let policy = |player: &str| {
+            let mut data = MediaData::default();
+            data.player = player.to_string();
+            config.report_player(&data)
+        };
+
+        // The rule matches the alias from [aliases] and replaces it.
+        assert_eq!(
+            policy("Mozilla Firefox Nightly"),
+            PlayerPolicy {
+                player: "Firefox Nightly".to_string(),
+                included: true,
+                report_paused: false,
+                min_duration: Duration::from_secs(30),
+                extra_fields: vec![],
+                bucket_id: Some("browser".to_string()),
+            }
+        );
+        assert_eq!(
+            policy("Spotify.exe"),
+            PlayerPolicy {
+                player: "Spotify".to_string(),
+                included: true,
+                report_paused: true,
+                min_duration: Duration::from_secs(10),
+                extra_fields: vec![
+                    ExtraField::Composer,
+                    ExtraField::Genre,
+                    ExtraField::TrackNumber
+                ],
+                bucket_id: None,
+            }
+        );
+        assert_eq!(
+            policy("Chromium"),
+            PlayerPolicy {
+                player: "Chromium".to_string(),
+                included: false,
+                report_paused: true,
+                min_duration: Duration::ZERO,
+                extra_fields: config.extra_fields.clone(),
+                bucket_id: None,
+            }
+        );
+    }
+
+    #[test]
+    fn report_site_filters_by_domain() {
+        let temp_dir = tempdir().unwrap();
+        let _guard = EnvGuard::set("XDG_CONFIG_HOME", temp_dir.path().as_os_str());
+
+        let cli = Cli {
+            config: None,
+            host: None,
+            port: None,
+            poll_interval: None,
+            bucket_id: None,
+            hostname: None,
+            bucket_per_player: None,
+            report_mode: None,
+            include_players: vec![],
+            exclude_players: vec![],
+            include_sites: vec![],
+            exclude_sites: vec!["YouTube.com".to_string()],
+            mpris_signals: None,
+            report_paused: None,
+            extra_fields: vec![],
+            ignore_muted: None,
+            queue_size: None,
+            mock_timeline: None,
+            dry_run: false,
+            strict: false,
+            verbosity: Verbosity::new(0, 1),
+            command: None,
+        };
+        let config = Config::new(cli);
+
+        assert!(!config.report_site(Some("youtube.com")));
+        assert!(!config.report_site(Some("music.youtube.com")));
+        assert!(config.report_site(Some("notyoutube.com")));
+        assert!(config.report_site(Some("soundcloud.com")));
+        assert!(config.report_site(None));
+
+        let cli = Cli {
+            config: None,
+            host: None,
+            port: None,
+            poll_interval: None,
+            bucket_id: None,
+            hostname: None,
+            bucket_per_player: None,
+            report_mode: None,
+            include_players: vec![],
+            exclude_players: vec![],
+            include_sites: vec!["soundcloud.com".to_string()],
+            exclude_sites: vec![],
+            mpris_signals: None,
+            report_paused: None,
+            extra_fields: vec![],
+            ignore_muted: None,
+            queue_size: None,
+            mock_timeline: None,
+            dry_run: false,
+            strict: false,
+            verbosity: Verbosity::new(0, 1),
+            command: None,
+        };
+        let config = Config::new(cli);
+
+        assert!(config.report_site(Some("soundcloud.com")));
+        assert!(!config.report_site(Some("youtube.com")));
+        assert!(!config.report_site(None));
     }
 }
@@ -0,0 +1,433 @@
+use std::{collections::BTreeMap, collections::BTreeSet, fmt, ops::Range};
+
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Serialize;
+use toml::{Spanned, Value};
+
+use super::{duration, Cli, Config, FilterRule, PlayerRule, RedactionRule, Toml};
+use crate::sink::SinkConfig;
+
+/// Structure of a TOML document with the positions of its keys, to tell where the unknown keys are.
+enum Node {
+    Table(Vec<(Spanned<String>, Node)>),
+    Array(Vec<Node>),
+    Value,
+}
+
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct NodeVisitor;
+
+        impl<'de> Visitor<'de> for NodeVisitor {
+            type Value = Node;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a TOML value")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Node, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(key) = map.next_key::<Spanned<String>>()? {
+                    entries.push((key, map.next_value()?));
+                }
+                Ok(Node::Table(entries))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Node, A::Error> {
+                let mut items = Vec::new();
+                while let Some(item) = seq.next_element()? {
+                    items.push(item);
+                }
+                Ok(Node::Array(items))
+            }
+
+            fn visit_bool<E>(self, _: bool) -> Result<Node, E> {
+                Ok(Node::Value)
+            }
+
+            fn visit_i64<E>(self, _: i64) -> Result<Node, E> {
+                Ok(Node::Value)
+            }
+
+            fn visit_u64<E>(self, _: u64) -> Result<Node, E> {
+                Ok(Node::Value)
+            }
+
+            fn visit_f64<E>(self, _: f64) -> Result<Node, E> {
+                Ok(Node::Value)
+            }
+
+            fn visit_str<E>(self, _: &str) -> Result<Node, E> {
+                Ok(Node::Value)
+            }
+        }
+
+        deserializer.deserialize_any(NodeVisitor)
+    }
+}
+
+/// Returns the top-level keys set in the file, and the keys which are not used by the parsed configuration
+/// with their positions, e.g. `"exclude_player" at line 3, column 1`.
+pub fn inspect_keys(content: &str, toml: &Toml) -> (BTreeSet<String>, Vec<String>) {
+    let Ok(Node::Table(entries)) = toml::from_str::<Node>(content) else {
+        return (BTreeSet::new(), Vec::new());
+    };
+    let Ok(known) = Value::try_from(toml) else {
+        return (BTreeSet::new(), Vec::new());
+    };
+
+    let keys = entries
+        .iter()
+        .map(|(key, _)| key.get_ref().clone())
+        .collect();
+    let mut unknown = Vec::new();
+    unknown_keys(&entries, &known, "", &mut |path, span| {
+        let (line, column) = position(content, span.start);
+        unknown.push(format!("\"{path}\" at line {line}, column {column}"));
+    });
+
+    (keys, unknown)
+}
+
+/// Calls `report` for every key of the table which is missing in the known value.
+fn unknown_keys(
+    entries: &[(Spanned<String>, Node)],
+    known: &Value,
+    path: &str,
+    report: &mut impl FnMut(&str, Range<usize>),
+) {
+    for (key, node) in entries {
+        let path = if path.is_empty() {
+            key.get_ref().clone()
+        } else {
+            format!("{path}.{}", key.get_ref())
+        };
+        match (node, known.get(key.get_ref())) {
+            (Node::Table(entries), Some(known)) => unknown_keys(entries, known, &path, report),
+            (Node::Array(items), Some(Value::Array(known))) => {
+                for (i, (item, known)) in items.iter().zip(known).enumerate() {
+                    if let Node::Table(entries) = item {
+                        unknown_keys(entries, known, &format!("{path}[{i}]"), report);
+                    }
+                }
+            }
+            // Empty lists are not serialized back.
+            (Node::Array(items), None) if items.is_empty() => {}
+            (_, None) => report(&path, key.span()),
+            _ => {}
+        }
+    }
+}
+
+/// One-based line and column of the byte offset.
+fn position(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .map_or(0, |line| line.chars().count())
+        + 1;
+
+    (line, column)
+}
+
+/// The first source of the value which is set.
+fn source(cli: bool, env: bool, file: bool) -> &'static str {
+    match (cli, env, file) {
+        (true, _, _) => "command line",
+        (_, true, _) => "environment",
+        (_, _, true) => "file",
+        _ => "default",
+    }
+}
+
+/// All sources of the list items.
+fn list_source(cli: bool, env: bool, file: bool) -> String {
+    let sources: Vec<_> = [(cli, "command line"), (env, "environment"), (file, "file")]
+        .into_iter()
+        .filter_map(|(set, source)| set.then_some(source))
+        .collect();
+    if sources.is_empty() {
+        "default".to_string()
+    } else {
+        sources.join(", ")
+    }
+}
+
+fn value(value: impl Serialize) -> Value {
+    Value::try_from(value).expect("Settings are serializable")
+}
+
+#[derive(Serialize)]
+struct Tables<'a> {
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    filters: &'a [FilterRule],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    redactions: &'a [RedactionRule],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    players: &'a [PlayerRule],
+    sinks: &'a [SinkConfig],
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    aliases: &'a BTreeMap<String, String>,
+}
+
+/// The settings with a single value, with their sources.
+fn settings(config: &Config, toml_data: &Toml) -> [(&'static str, Value, &'static str); 11] {
+    let (cli, env) = (&config.cli, &config.env);
+    let file = |key: &str| toml_data.file_keys.contains(key);
+
+    [
+        (
+            "host",
+            value(&config.host),
+            source(cli.host.is_some(), env.host.is_some(), file("host")),
+        ),
+        (
+            "port",
+            value(config.port),
+            source(cli.port.is_some(), env.port.is_some(), file("port")),
+        ),
+        (
+            "poll_time",
+            value(duration::format(config.poll_interval)),
+            source(
+                cli.poll_interval.is_some(),
+                env.poll_interval.is_some(),
+                file("poll_time"),
+            ),
+        ),
+        (
+            "bucket_id",
+            value(&config.bucket_id),
+            source(
+                cli.bucket_id.is_some(),
+                env.bucket_id.is_some(),
+                file("bucket_id"),
+            ),
+        ),
+        (
+            "hostname",
+            value(&config.hostname),
+            match source(
+                cli.hostname.is_some(),
+                env.hostname.is_some(),
+                file("hostname"),
+            ) {
+                "default" => "system hostname",
+                source => source,
+            },
+        ),
+        (
+            "bucket_per_player",
+            value(config.bucket_per_player),
+            source(
+                cli.bucket_per_player.is_some(),
+                env.bucket_per_player.is_some(),
+                file("bucket_per_player"),
+            ),
+        ),
+        (
+            "report_mode",
+            value(config.report_mode),
+            source(
+                cli.report_mode.is_some(),
+                env.report_mode.is_some(),
+                file("report_mode"),
+            ),
+        ),
+        (
+            "mpris_signals",
+            value(config.mpris_signals),
+            source(
+                cli.mpris_signals.is_some(),
+                env.mpris_signals.is_some(),
+                file("mpris_signals"),
+            ),
+        ),
+        (
+            "report_paused",
+            value(config.report_paused),
+            source(
+                cli.report_paused.is_some(),
+                env.report_paused.is_some(),
+                file("report_paused"),
+            ),
+        ),
+        (
+            "ignore_muted",
+            value(config.ignore_muted),
+            source(
+                cli.ignore_muted.is_some(),
+                env.ignore_muted.is_some(),
+                file("ignore_muted"),
+            ),
+        ),
+        (
+            "queue_size",
+            value(config.queue_size),
+            source(
+                cli.queue_size.is_some(),
+                env.queue_size.is_some(),
+                file("queue_size"),
+            ),
+        ),
+    ]
+}
+
+/// The settings with lists combined from several sources, with their sources.
+fn lists(config: &Config, toml_data: &Toml) -> [(&'static str, Value, String); 5] {
+    let (cli, env) = (&config.cli, &config.env);
+
+    [
+        (
+            "include_players",
+            value(&config.include_players),
+            list_source(
+                !cli.include_players.is_empty(),
+                !env.include_players.is_empty(),
+                !toml_data.include_players.is_empty(),
+            ),
+        ),
+        (
+            "exclude_players",
+            value(&config.exclude_players),
+            list_source(
+                !cli.exclude_players.is_empty(),
+                !env.exclude_players.is_empty(),
+                !toml_data.exclude_players.is_empty(),
+            ),
+        ),
+        (
+            "include_sites",
+            value(&config.include_sites),
+            list_source(
+                !cli.include_sites.is_empty(),
+                !env.include_sites.is_empty(),
+                !toml_data.include_sites.is_empty(),
+            ),
+        ),
+        (
+            "exclude_sites",
+            value(&config.exclude_sites),
+            list_source(
+                !cli.exclude_sites.is_empty(),
+                !env.exclude_sites.is_empty(),
+                !toml_data.exclude_sites.is_empty(),
+            ),
+        ),
+        (
+            "extra_fields",
+            value(&config.extra_fields),
+            list_source(
+                !cli.extra_fields.is_empty(),
+                !env.extra_fields.is_empty(),
+                !toml_data.extra_fields.is_empty(),
+            ),
+        ),
+    ]
+}
+
+/// Prints the effective configuration with the source of every value.
+/// Fails if the configuration has problems, e.g. to check it before deploying.
+pub fn check_config(cli: Cli) -> anyhow::Result<()> {
+    let (config, toml_data) = Config::load(cli);
+    match &config.file {
+        Some(file) if file.exists() => println!("# Configuration file: {}", file.display()),
+        Some(file) => println!("# Configuration file: {} (missing)", file.display()),
+        None => println!("# No configuration file"),
+    }
+    if let Some(migration) = &toml_data.migration {
+        println!(
+            "# The file has version {}, it will be migrated to version {} when the watcher starts",
+            migration.from, toml_data.version
+        );
+    }
+
+    for (key, value, source) in settings(&config, &toml_data) {
+        println!("{key} = {value} # {source}");
+    }
+
+    for (key, value, source) in lists(&config, &toml_data) {
+        println!("{key} = {value} # {source}");
+    }
+
+    println!();
+    if toml_data.sinks.is_empty() {
+        println!("# The tables are from the file, except the default sink");
+    } else {
+        println!("# The tables are from the file");
+    }
+    print!(
+        "{}",
+        toml::to_string(&Tables {
+            filters: &toml_data.filters,
+            redactions: &toml_data.redactions,
+            players: &toml_data.players,
+            sinks: &config.sinks,
+            aliases: &toml_data.aliases,
+        })?
+    );
+
+    let count = config.errors.len() + config.problems.len();
+    if count == 0 {
+        return Ok(());
+    }
+    for problem in config.errors.iter().chain(&config.problems) {
+        eprintln!("{problem}");
+    }
+    anyhow::bail!("The configuration has {count} problem(s)")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_unknown_keys_with_positions() {
+        let content = r#"port = 1234
+exclude_player = ["Firefox"]
+filters = []
+
+[[redactions]]
+fields = ["title"]
+placehodler = "Private"
+
+[[sinks]]
+type = "activitywatch"
+
+[[sinks]]
+type = "file"
+path = "listening.csv"
+max_sise = 1024
+
+[aliases]
+"Spotify.exe" = "Spotify"
+"#;
+        let toml: Toml = toml::from_str(content).unwrap();
+
+        let (keys, unknown) = inspect_keys(content, &toml);
+        assert_eq!(
+            keys,
+            BTreeSet::from(
+                [
+                    "port",
+                    "exclude_player",
+                    "filters",
+                    "redactions",
+                    "sinks",
+                    "aliases"
+                ]
+                .map(str::to_string)
+            )
+        );
+        assert_eq!(
+            unknown,
+            vec![
+                r#""exclude_player" at line 2, column 1"#,
+                r#""redactions[0].placehodler" at line 7, column 1"#,
+                r#""sinks[1].max_sise" at line 15, column 1"#,
+            ]
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use serde::{de, Deserialize, Deserializer, Serializer};
+
+/// Parses a duration of one or more parts with units: "ms", "s", "m" or "h", e.g. "5s" or "1m30s".
+pub fn parse(text: &str) -> anyhow::Result<Duration> {
+    let text = text.trim();
+    if text.is_empty() {
+        anyhow::bail!("The duration is empty");
+    }
+
+    let mut duration = Duration::ZERO;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let number: u64 = rest[..digits]
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Expected a number in the duration \"{text}\""))?;
+        rest = &rest[digits..];
+
+        let unit = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let part = match &rest[..unit] {
+            "ms" => Some(Duration::from_millis(number)),
+            "s" => Some(Duration::from_secs(number)),
+            "m" => number.checked_mul(60).map(Duration::from_secs),
+            "h" => number.checked_mul(60 * 60).map(Duration::from_secs),
+            "" => anyhow::bail!("Missing the unit in the duration \"{text}\", e.g. \"{number}s\""),
+            unit => anyhow::bail!("Unknown unit \"{unit}\" in the duration \"{text}\""),
+        };
+        duration = part
+            .and_then(|part| duration.checked_add(part))
+            .with_context(|| format!("The duration \"{text}\" is too long"))?;
+        rest = &rest[unit..];
+    }
+
+    Ok(duration)
+}
+
+/// Formats the duration in seconds, or in milliseconds if it's not a whole number of seconds.
+pub fn format(duration: Duration) -> String {
+    if duration.subsec_millis() == 0 {
+        format!("{}s", duration.as_secs())
+    } else {
+        format!("{}ms", duration.as_millis())
+    }
+}
+
+pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format(*duration))
+}
+
+/// Reads the duration from a string, or from a number of seconds as written before the durations.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Text {
+        Seconds(u64),
+        Text(String),
+    }
+
+    match Text::deserialize(deserializer)? {
+        Text::Seconds(secs) => Ok(Duration::from_secs(secs)),
+        Text::Text(text) => parse(&text).map_err(de::Error::custom),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_formats_durations() {
+        assert_eq!(parse("5s").unwrap(), Duration::from_secs(5));
+        assert_eq!(parse(" 1m30s ").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse("1h").unwrap(), Duration::from_hours(1));
+        assert!(parse(&format!("{}h", u64::MAX)).is_err());
+        assert_eq!(parse("2s500ms").unwrap(), Duration::from_millis(2500));
+        for invalid in ["", "5", "s", "5 s", "5d", "-5s"] {
+            assert!(parse(invalid).is_err(), "{invalid} should be rejected");
+        }
+
+        assert_eq!(format(Duration::from_secs(90)), "90s");
+        assert_eq!(format(Duration::from_millis(2500)), "2500ms");
+    }
+}
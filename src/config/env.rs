@@ -0,0 +1,213 @@
+use std::{fmt::Display, path::PathBuf, str::FromStr, time::Duration};
+
+use clap::ValueEnum;
+
+use super::Toml;
+use crate::platform::ExtraField;
+use crate::watcher::ReportMode;
+
+/// Prefix of the environment variables, e.g. `AW_MEDIA_HOST` for `host`.
+const PREFIX: &str = "AW_MEDIA_";
+
+/// Settings from the `AW_MEDIA_*` environment variables,
+/// which override the configuration file and are overridden by the command line.
+#[derive(Debug, Default, Clone)]
+pub struct Env {
+    pub config: Option<PathBuf>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub bucket_id: Option<String>,
+    pub hostname: Option<String>,
+    pub bucket_per_player: Option<bool>,
+    pub report_mode: Option<ReportMode>,
+    pub poll_interval: Option<u64>,
+    pub include_players: Vec<String>,
+    pub exclude_players: Vec<String>,
+    pub include_sites: Vec<String>,
+    pub exclude_sites: Vec<String>,
+    pub mpris_signals: Option<bool>,
+    pub report_paused: Option<bool>,
+    pub extra_fields: Vec<ExtraField>,
+    pub ignore_muted: Option<bool>,
+    pub queue_size: Option<usize>,
+    /// Invalid values which are ignored.
+    pub problems: Vec<String>,
+}
+
+impl Env {
+    pub fn read() -> Self {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Reads the settings with the variable getter, ignoring the empty and invalid values.
+    /// The invalid values are returned as the problems.
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let mut vars = Vars {
+            var,
+            problems: Vec::new(),
+        };
+        let mut extra_fields = Vec::new();
+        for field in vars.list("EXTRA_FIELDS") {
+            match ExtraField::from_str(&field, true) {
+                Ok(field) => extra_fields.push(field),
+                Err(e) => vars.problems.push(format!(
+                    "Ignoring {PREFIX}EXTRA_FIELDS item \"{field}\": {e}"
+                )),
+            }
+        }
+
+        Self {
+            config: vars.get("CONFIG").map(PathBuf::from),
+            host: vars.get("HOST"),
+            port: vars.parse("PORT"),
+            bucket_id: vars.get("BUCKET_ID"),
+            hostname: vars.get("HOSTNAME"),
+            bucket_per_player: vars.parse("BUCKET_PER_PLAYER"),
+            report_mode: vars.value_enum("REPORT_MODE"),
+            poll_interval: vars.parse("POLL_INTERVAL"),
+            include_players: vars.list("INCLUDE_PLAYERS"),
+            exclude_players: vars.list("EXCLUDE_PLAYERS"),
+            include_sites: vars.list("INCLUDE_SITES"),
+            exclude_sites: vars.list("EXCLUDE_SITES"),
+            mpris_signals: vars.parse("MPRIS_SIGNALS"),
+            report_paused: vars.parse("REPORT_PAUSED"),
+            extra_fields,
+            ignore_muted: vars.parse("IGNORE_MUTED"),
+            queue_size: vars.parse("QUEUE_SIZE"),
+            problems: vars.problems,
+        }
+    }
+
+    /// Overrides the settings from the file with the variables which are set,
+    /// the listed items are added before the ones from the file.
+    pub fn apply(&self, toml: &mut Toml) {
+        fn set<T: Clone>(target: &mut T, value: Option<&T>) {
+            if let Some(value) = value {
+                target.clone_from(value);
+            }
+        }
+        fn prepend<T: Clone>(target: &mut Vec<T>, items: &[T]) {
+            target.splice(0..0, items.iter().cloned());
+        }
+
+        set(&mut toml.host, self.host.as_ref());
+        set(&mut toml.port, self.port.as_ref());
+        set(&mut toml.bucket_id, self.bucket_id.as_ref());
+        if self.hostname.is_some() {
+            toml.hostname.clone_from(&self.hostname);
+        }
+        set(&mut toml.bucket_per_player, self.bucket_per_player.as_ref());
+        set(&mut toml.report_mode, self.report_mode.as_ref());
+        set(
+            &mut toml.poll_time,
+            self.poll_interval.map(Duration::from_secs).as_ref(),
+        );
+        prepend(&mut toml.include_players, &self.include_players);
+        prepend(&mut toml.exclude_players, &self.exclude_players);
+        prepend(&mut toml.include_sites, &self.include_sites);
+        prepend(&mut toml.exclude_sites, &self.exclude_sites);
+        set(&mut toml.mpris_signals, self.mpris_signals.as_ref());
+        set(&mut toml.report_paused, self.report_paused.as_ref());
+        prepend(&mut toml.extra_fields, &self.extra_fields);
+        set(&mut toml.ignore_muted, self.ignore_muted.as_ref());
+        set(&mut toml.queue_size, self.queue_size.as_ref());
+    }
+}
+
+/// The variable getter, collecting the invalid values.
+struct Vars<F> {
+    var: F,
+    problems: Vec<String>,
+}
+
+impl<F: Fn(&str) -> Option<String>> Vars<F> {
+    fn get(&self, name: &str) -> Option<String> {
+        (self.var)(&format!("{PREFIX}{name}")).filter(|value| !value.trim().is_empty())
+    }
+
+    fn parse<T>(&mut self, name: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let value = self.get(name)?;
+        value
+            .trim()
+            .parse()
+            .map_err(|e| self.invalid(name, &value, e))
+            .ok()
+    }
+
+    fn value_enum<T: ValueEnum>(&mut self, name: &str) -> Option<T> {
+        let value = self.get(name)?;
+        T::from_str(value.trim(), true)
+            .map_err(|e| self.invalid(name, &value, e))
+            .ok()
+    }
+
+    fn invalid(&mut self, name: &str, value: &str, error: impl Display) {
+        self.problems
+            .push(format!("Ignoring {PREFIX}{name}=\"{value}\": {error}"));
+    }
+
+    /// Comma-separated list, like the list options on the command line.
+    fn list(&self, name: &str) -> Vec<String> {
+        self.get(name)
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> Env {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| ((*name).to_string(), (*value).to_string()))
+            .collect();
+        Env::from_vars(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn reads_prefixed_variables_and_ignores_invalid_values() {
+        let env = env(&[
+            ("AW_MEDIA_HOST", "aw.local"),
+            ("AW_MEDIA_PORT", "not a port"),
+            ("AW_MEDIA_POLL_INTERVAL", " 3 "),
+            ("AW_MEDIA_EXCLUDE_PLAYERS", "Firefox, ,Chromium"),
+            ("AW_MEDIA_REPORT_MODE", "Track"),
+            ("AW_MEDIA_EXTRA_FIELDS", "genre,unknown"),
+            ("AW_MEDIA_IGNORE_MUTED", "true"),
+            ("AW_MEDIA_HOSTNAME", ""),
+            ("HOST", "ignored"),
+        ]);
+
+        assert_eq!(env.host.as_deref(), Some("aw.local"));
+        assert_eq!(env.port, None);
+        assert_eq!(env.poll_interval, Some(3));
+        assert_eq!(env.exclude_players, vec!["Firefox", "Chromium"]);
+        assert_eq!(env.report_mode, Some(ReportMode::Track));
+        assert_eq!(env.extra_fields, vec![ExtraField::Genre]);
+        assert_eq!(env.ignore_muted, Some(true));
+        assert_eq!(env.hostname, None);
+        assert_eq!(env.queue_size, None);
+        assert_eq!(
+            env.problems,
+            [
+                "Ignoring AW_MEDIA_EXTRA_FIELDS item \"unknown\": invalid variant: unknown",
+                "Ignoring AW_MEDIA_PORT=\"not a port\": invalid digit found in string",
+            ]
+        );
+    }
+}
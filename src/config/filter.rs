@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::platform::MediaData;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterAction {
+    /// Only the media matching any of the include filters is reported.
+    Include,
+    /// The media matching any of the exclude filters is not reported.
+    Exclude,
+}
+
+/// A filter in the configuration with the regexes for the fields of the media, such as `title` or `uri`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FilterRule {
+    pub action: FilterAction,
+    #[serde(flatten)]
+    pub fields: BTreeMap<String, String>,
+}
+
+/// Regexes for the fields of the media, which match it if every field matches its regex.
+/// The media without the field doesn't match.
+#[derive(Clone)]
+pub struct FieldMatcher(Vec<(String, Regex)>);
+
+impl FieldMatcher {
+    /// Compiles the regexes, failing on the fields which the media never has, e.g. misspelled ones.
+    pub fn new(fields: &BTreeMap<String, String>) -> anyhow::Result<Self> {
+        if let Some(field) = fields.keys().find(|field| !MediaData::is_field(field)) {
+            anyhow::bail!("Unknown field \"{field}\" to match");
+        }
+
+        fields
+            .iter()
+            .map(|(field, pattern)| {
+                Regex::new(pattern)
+                    .map(|regex| (field.clone(), regex))
+                    .with_context(|| format!("Invalid regex for {field}"))
+            })
+            .collect::<anyhow::Result<_>>()
+            .map(Self)
+    }
+
+    pub fn matches(&self, data: &Map<String, Value>) -> bool {
+        self.0.iter().all(|(field, regex)| match data.get(field) {
+            Some(Value::String(value)) => regex.is_match(value),
+            Some(value) => regex.is_match(&value.to_string()),
+            None => false,
+        })
+    }
+
+    fn describe(&self) -> String {
+        self.0
+            .iter()
+            .map(|(field, regex)| format!("{field} = \"{}\"", regex.as_str()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Serialized media for the tests of the rules matching it.
+#[cfg(test)]
+pub fn media(value: Value) -> Map<String, Value> {
+    let Value::Object(data) = value else {
+        panic!("Media must be an object");
+    };
+    data
+}
+
+/// A compiled filter.
+pub struct Filter {
+    action: FilterAction,
+    matcher: FieldMatcher,
+}
+
+impl Filter {
+    pub fn new(rule: &FilterRule) -> anyhow::Result<Self> {
+        if rule.fields.is_empty() {
+            anyhow::bail!("The filter has no fields to match");
+        }
+
+        Ok(Self {
+            action: rule.action,
+            matcher: FieldMatcher::new(&rule.fields)?,
+        })
+    }
+}
+
+/// Returns why the serialized media is filtered out: it matches an exclude filter,
+/// or it doesn't match any include filter if there are some.
+pub fn filter_reason(filters: &[Filter], data: &Map<String, Value>) -> Option<String> {
+    if let Some(filter) = filters
+        .iter()
+        .find(|filter| filter.action == FilterAction::Exclude && filter.matcher.matches(data))
+    {
+        return Some(format!(
+            "the media matches the exclude filter {}",
+            filter.matcher.describe()
+        ));
+    }
+
+    let mut includes = filters
+        .iter()
+        .filter(|filter| filter.action == FilterAction::Include)
+        .peekable();
+    if includes.peek().is_some() && !includes.any(|filter| filter.matcher.matches(data)) {
+        return Some("the media matches no include filter".to_string());
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn filter(action: FilterAction, fields: &[(&str, &str)]) -> Filter {
+        Filter::new(&FilterRule {
+            action,
+            fields: fields
+                .iter()
+                .map(|(field, pattern)| ((*field).to_string(), (*pattern).to_string()))
+                .collect(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn exclude_filters_match_every_field() {
+        let filters = [filter(
+            FilterAction::Exclude,
+            &[("player", "(?i)firefox"), ("title", "(?i)standup|meeting")],
+        )];
+
+        let meeting = media(json!({ "player": "Firefox", "title": "Weekly Meeting" }));
+        assert_eq!(
+            filter_reason(&filters, &meeting).as_deref(),
+            Some(
+                r#"the media matches the exclude filter player = "(?i)firefox", title = "(?i)standup|meeting""#
+            )
+        );
+        let music = media(json!({ "player": "Firefox", "title": "Song" }));
+        assert_eq!(filter_reason(&filters, &music), None);
+        let untitled = media(json!({ "player": "Firefox" }));
+        assert_eq!(filter_reason(&filters, &untitled), None);
+    }
+
+    #[test]
+    fn include_filters_require_any_match() {
+        let filters = [
+            filter(FilterAction::Include, &[("uri", "^file://")]),
+            filter(FilterAction::Include, &[("track_number", "^[0-9]+$")]),
+            filter(
+                FilterAction::Exclude,
+                &[("uri", "^file:///home/user/private/")],
+            ),
+        ];
+
+        let local = media(json!({ "uri": "file:///music/a.flac" }));
+        assert_eq!(filter_reason(&filters, &local), None);
+        let numbered = media(json!({ "uri": "https://example.com", "track_number": 3 }));
+        assert_eq!(filter_reason(&filters, &numbered), None);
+        let stream = media(json!({ "uri": "https://example.com" }));
+        assert_eq!(
+            filter_reason(&filters, &stream).as_deref(),
+            Some("the media matches no include filter")
+        );
+        let private = media(json!({ "uri": "file:///home/user/private/a.flac" }));
+        assert!(filter_reason(&filters, &private).is_some());
+    }
+
+    #[test]
+    fn filters_match_playback_fields() {
+        let filters = [filter(
+            FilterAction::Exclude,
+            &[("volume", "^0(\\.0)?$"), ("loop_status", "^track$")],
+        )];
+
+        let looped = media(json!({ "volume": 0.0, "loop_status": "track" }));
+        assert!(filter_reason(&filters, &looped).is_some());
+        let audible = media(json!({ "volume": 0.5, "loop_status": "track" }));
+        assert_eq!(filter_reason(&filters, &audible), None);
+    }
+
+    #[test]
+    fn invalid_filters_are_rejected() {
+        let mut rule = FilterRule {
+            action: FilterAction::Exclude,
+            fields: BTreeMap::new(),
+        };
+        assert!(Filter::new(&rule).is_err());
+
+        rule.fields.insert("title".to_string(), "(".to_string());
+        assert!(Filter::new(&rule).is_err());
+
+        rule.fields = BTreeMap::from([("tilte".to_string(), "meeting".to_string())]);
+        let Err(error) = Filter::new(&rule) else {
+            panic!("The unknown field should be rejected");
+        };
+        assert_eq!(error.to_string(), "Unknown field \"tilte\" to match");
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use toml_edit::{DocumentMut, Value};
+
+/// Migrations of the configuration from every version, starting with 1, to the next one.
+const MIGRATIONS: &[fn(&mut DocumentMut)] = &[poll_time_to_duration];
+
+/// Version of the configuration written by this watcher.
+pub const CURRENT_VERSION: usize = MIGRATIONS.len() + 1;
+
+/// Version of the configuration, the files without it predate the versions.
+fn version(document: &DocumentMut) -> anyhow::Result<usize> {
+    let Some(version) = document.get("version") else {
+        return Ok(1);
+    };
+
+    version
+        .as_integer()
+        .and_then(|version| usize::try_from(version).ok())
+        .filter(|version| *version > 0)
+        .context("The version must be a positive integer")
+}
+
+/// Rewrites the configuration of an older version into the current shape, keeping the comments.
+/// Returns the version it had, or `None` if it's already current.
+pub fn migrate(document: &mut DocumentMut) -> anyhow::Result<Option<usize>> {
+    let version = version(document)?;
+    if version > CURRENT_VERSION {
+        anyhow::bail!(
+            "The configuration version {version} is newer than {CURRENT_VERSION} supported by this watcher"
+        );
+    }
+    if version == CURRENT_VERSION {
+        return Ok(None);
+    }
+
+    for migration in &MIGRATIONS[version - 1..] {
+        migration(document);
+    }
+    document["version"] = toml_edit::value(i64::try_from(CURRENT_VERSION)?);
+
+    Ok(Some(version))
+}
+
+/// Configuration migrated from an older version in memory, which isn't saved to its file yet.
+#[derive(Debug, Clone)]
+pub struct Migration {
+    /// Version the file has.
+    pub from: usize,
+    /// The migrated file, keeping the comments.
+    pub content: String,
+}
+
+impl Migration {
+    /// Replaces the file with the migrated configuration, keeping the original as a backup.
+    pub fn save(&self, file: &Path) {
+        match rewrite(file, self.from, &self.content) {
+            Ok(backup) => info!(
+                "Migrated config file {} from version {}, the original is backed up to {}",
+                file.display(),
+                self.from,
+                backup.display()
+            ),
+            Err(e) => warn!("{e:#}, the migrated configuration is used without saving it"),
+        }
+    }
+}
+
+/// Backs up the file with the older version, e.g. to "aw-watcher-media-player.toml.v1.bak",
+/// and replaces it with the migrated configuration. Returns the backup file.
+/// An existing backup is never overwritten, the next one is numbered, e.g. "aw-watcher-media-player.toml.v1.2.bak".
+fn rewrite(file: &Path, version: usize, content: &str) -> anyhow::Result<PathBuf> {
+    let original =
+        fs::read(file).with_context(|| format!("Failed to read config file {}", file.display()))?;
+
+    let mut number = 1;
+    let (backup, mut backup_file) = loop {
+        let mut backup = file.as_os_str().to_owned();
+        if number == 1 {
+            backup.push(format!(".v{version}.bak"));
+        } else {
+            backup.push(format!(".v{version}.{number}.bak"));
+        }
+        let backup = PathBuf::from(backup);
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&backup)
+        {
+            Ok(backup_file) => break (backup, backup_file),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => number += 1,
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!("Failed to back up config file to {}", backup.display())
+                })
+            }
+        }
+    };
+    backup_file
+        .write_all(&original)
+        .with_context(|| format!("Failed to back up config file to {}", backup.display()))?;
+    fs::write(file, content)
+        .with_context(|| format!("Failed to write migrated config file {}", file.display()))?;
+
+    Ok(backup)
+}
+
+/// Version 2: `poll_time` is a duration string, e.g. "5s", instead of a number of seconds.
+fn poll_time_to_duration(document: &mut DocumentMut) {
+    let Some(value) = document
+        .get_mut("poll_time")
+        .and_then(|item| item.as_value_mut())
+    else {
+        return;
+    };
+    if let Some(secs) = value.as_integer() {
+        let decor = value.decor().clone();
+        *value = Value::from(format!("{secs}s"));
+        *value.decor_mut() = decor;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_legacy_config_keeping_comments() {
+        let mut document: DocumentMut = "# Media\nport = 1234\npoll_time = 10 # often\n"
+            .parse()
+            .unwrap();
+
+        assert_eq!(migrate(&mut document).unwrap(), Some(1));
+        assert_eq!(
+            document.to_string(),
+            "# Media\nport = 1234\npoll_time = \"10s\" # often\nversion = 2\n"
+        );
+        assert_eq!(migrate(&mut document).unwrap(), None);
+    }
+
+    #[test]
+    fn keeps_existing_backups() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = temp_dir.path().join("config.toml");
+        std::fs::write(&file, "poll_time = 5").unwrap();
+        std::fs::write(temp_dir.path().join("config.toml.v1.bak"), "earlier").unwrap();
+
+        let backup = rewrite(&file, 1, "poll_time = \"5s\"").unwrap();
+        assert_eq!(backup, temp_dir.path().join("config.toml.v1.2.bak"));
+        assert_eq!(std::fs::read_to_string(backup).unwrap(), "poll_time = 5");
+        assert_eq!(
+            std::fs::read_to_string(temp_dir.path().join("config.toml.v1.bak")).unwrap(),
+            "earlier"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "poll_time = \"5s\""
+        );
+    }
+
+    #[test]
+    fn rejects_newer_and_invalid_versions() {
+        for content in ["version = 3", "version = 0", "version = \"2\""] {
+            let mut document: DocumentMut = content.parse().unwrap();
+            assert!(
+                migrate(&mut document).is_err(),
+                "{content} should be rejected"
+            );
+        }
+    }
+}
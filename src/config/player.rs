@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::duration;
+use crate::platform::ExtraField;
+
+/// A `[[players]]` entry in the configuration, overriding the settings for the matching players.
+/// The settings which are not set are the global ones.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlayerRule {
+    /// Case-insensitive substring of the player name or its alias, like the player filters.
+    pub player: String,
+    /// Name to report the player as, instead of its alias from `[aliases]`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report_paused: Option<bool>,
+    /// Plays shorter than this are not reported, e.g. skipped tracks.
+    #[serde(default, with = "duration", skip_serializing_if = "Duration::is_zero")]
+    pub min_duration: Duration,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra_fields: Option<Vec<ExtraField>>,
+    /// Bucket ID to report the player to instead of the configured one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bucket_id: Option<String>,
+}
+
+impl PlayerRule {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.player.trim().is_empty() {
+            anyhow::bail!("The player to match is empty");
+        }
+        if self
+            .bucket_id
+            .as_deref()
+            .is_some_and(|id| id.trim().is_empty())
+        {
+            anyhow::bail!("The bucket ID is empty");
+        }
+
+        Ok(())
+    }
+
+    /// Whether the rule applies to the player, by its name or its alias.
+    pub fn matches(&self, player: &str, alias: Option<&str>) -> bool {
+        let pattern = self.player.to_lowercase();
+        player.to_lowercase().contains(&pattern)
+            || alias.is_some_and(|alias| alias.to_lowercase().contains(&pattern))
+    }
+}
+
+/// How the media of a player is reported, resolved from its `[[players]]` entry and the global settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerPolicy {
+    /// Name the player is reported as.
+    pub player: String,
+    /// Whether the player passes the include and exclude lists.
+    pub included: bool,
+    pub report_paused: bool,
+    pub min_duration: Duration,
+    pub extra_fields: Vec<ExtraField>,
+    /// Bucket ID replacing the configured one, if the player has its own.
+    pub bucket_id: Option<String>,
+}
@@ -0,0 +1,201 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+use super::filter::FieldMatcher;
+use crate::platform::MediaData;
+
+fn default_placeholder() -> String {
+    String::from("[redacted]")
+}
+
+/// A redaction in the configuration, which hides the content of the media but keeps the time it played.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RedactionRule {
+    /// Regexes for the fields of the media the redaction applies to, it applies to all media if empty.
+    #[serde(default, rename = "match", skip_serializing_if = "BTreeMap::is_empty")]
+    pub matches: BTreeMap<String, String>,
+    /// Fields to redact, such as `title` or `uri`.
+    pub fields: Vec<String>,
+    /// Text to replace the fields with.
+    #[serde(default = "default_placeholder")]
+    pub placeholder: String,
+    /// Replaces the fields with their hashes with this salt instead of the placeholder,
+    /// so that the plays of the same media can still be told apart.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salt: Option<String>,
+}
+
+#[derive(Clone)]
+enum Replacement {
+    Placeholder(String),
+    Hash { salt: String },
+}
+
+/// A compiled redaction, applied to the media when it's serialized.
+#[derive(Clone)]
+pub struct Redaction {
+    matcher: FieldMatcher,
+    fields: Vec<String>,
+    replacement: Replacement,
+}
+
+impl Redaction {
+    pub fn new(rule: &RedactionRule) -> anyhow::Result<Self> {
+        if rule.fields.is_empty() {
+            anyhow::bail!("The redaction has no fields to redact");
+        }
+        if let Some(field) = rule.fields.iter().find(|field| !MediaData::is_field(field)) {
+            anyhow::bail!("Unknown field \"{field}\" to redact");
+        }
+
+        Ok(Self {
+            matcher: FieldMatcher::new(&rule.matches)?,
+            fields: rule.fields.clone(),
+            replacement: match &rule.salt {
+                Some(salt) => Replacement::Hash { salt: salt.clone() },
+                None => Replacement::Placeholder(rule.placeholder.clone()),
+            },
+        })
+    }
+
+    /// Redacts the serialized media by every redaction matching all fields of the media,
+    /// including the ones which are not reported, like the filters.
+    pub fn apply_all(
+        redactions: &[Redaction],
+        fields: &Map<String, Value>,
+        data: &mut Map<String, Value>,
+    ) {
+        for redaction in redactions {
+            if redaction.matcher.matches(fields) {
+                redaction.apply(data);
+            }
+        }
+    }
+
+    fn apply(&self, data: &mut Map<String, Value>) {
+        for field in &self.fields {
+            if let Some(value) = data.get_mut(field) {
+                *value = Value::String(self.replace(value));
+            }
+        }
+    }
+
+    fn replace(&self, value: &Value) -> String {
+        match &self.replacement {
+            Replacement::Placeholder(placeholder) => placeholder.clone(),
+            Replacement::Hash { salt } => {
+                let value = match value {
+                    Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                };
+                let mut hasher = Sha256::new();
+                hasher.update(salt.as_bytes());
+                hasher.update([0]);
+                hasher.update(value.as_bytes());
+
+                // 64 bits are enough to tell the media apart.
+                let mut hash = String::with_capacity(16);
+                for byte in &hasher.finalize()[..8] {
+                    let _ = write!(hash, "{byte:02x}");
+                }
+                hash
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::config::filter::media;
+
+    fn redaction(matches: &[(&str, &str)], fields: &[&str], salt: Option<&str>) -> Redaction {
+        Redaction::new(&RedactionRule {
+            matches: matches
+                .iter()
+                .map(|(field, pattern)| ((*field).to_string(), (*pattern).to_string()))
+                .collect(),
+            fields: fields.iter().map(|field| (*field).to_string()).collect(),
+            placeholder: default_placeholder(),
+            salt: salt.map(str::to_string),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn redacts_matching_media_with_placeholder() {
+        let redactions = [redaction(
+            &[("player", "(?i)firefox"), ("title", "Meeting")],
+            &["title", "uri", "album"],
+            None,
+        )];
+
+        let mut meeting =
+            media(json!({ "player": "Firefox", "title": "Meeting", "uri": "https://meet" }));
+        Redaction::apply_all(&redactions, &meeting.clone(), &mut meeting);
+        assert_eq!(
+            Value::Object(meeting),
+            json!({ "player": "Firefox", "title": "[redacted]", "uri": "[redacted]" })
+        );
+
+        let mut music = media(json!({ "player": "Firefox", "title": "Song" }));
+        Redaction::apply_all(&redactions, &music.clone(), &mut music);
+        assert_eq!(music["title"], json!("Song"));
+    }
+
+    #[test]
+    fn matches_fields_which_are_not_reported() {
+        let redactions = [redaction(&[("genre", "Podcast")], &["title"], None)];
+        let fields = media(json!({ "title": "Episode 1", "genre": "Podcast" }));
+
+        let mut data = media(json!({ "title": "Episode 1" }));
+        Redaction::apply_all(&redactions, &fields, &mut data);
+        assert_eq!(Value::Object(data), json!({ "title": "[redacted]" }));
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let rule = |fields: &[&str]| RedactionRule {
+            matches: BTreeMap::new(),
+            fields: fields.iter().map(|field| (*field).to_string()).collect(),
+            placeholder: default_placeholder(),
+            salt: None,
+        };
+
+        assert!(Redaction::new(&rule(&["title", "genre", "site"])).is_ok());
+        let Err(error) = Redaction::new(&rule(&["title", "titel"])) else {
+            panic!("The unknown field should be rejected");
+        };
+        assert_eq!(error.to_string(), "Unknown field \"titel\" to redact");
+    }
+
+    #[test]
+    fn hashes_are_salted_and_stable() {
+        let redactions = [
+            redaction(&[], &["artist"], Some("salt")),
+            // Matched before the artist is redacted.
+            redaction(&[("artist", "^Anathema$")], &["title"], Some("other")),
+        ];
+
+        let mut first = media(json!({ "artist": "Anathema", "title": "Untouchable" }));
+        Redaction::apply_all(&redactions, &first.clone(), &mut first);
+        let mut second = media(json!({ "artist": "Anathema", "title": "Untouchable" }));
+        Redaction::apply_all(&redactions, &second.clone(), &mut second);
+        assert_eq!(first, second);
+
+        let artist = first["artist"].as_str().unwrap();
+        assert_eq!(artist.len(), 16);
+        assert_ne!(artist, "Anathema");
+        assert_ne!(first["title"], json!("Untouchable"));
+        assert_ne!(
+            redaction(&[], &["artist"], Some("pepper")).replace(&json!("Anathema")),
+            artist
+        );
+    }
+}
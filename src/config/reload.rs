@@ -0,0 +1,100 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
+
+use super::Config;
+
+/// Tells when the configuration should be reloaded:
+/// the configuration file is modified, or SIGHUP is received on Unix.
+pub struct Reloader {
+    file: Option<PathBuf>,
+    modified: Option<SystemTime>,
+    hangup: Arc<AtomicBool>,
+}
+
+impl Reloader {
+    pub fn new(config: &Config) -> Self {
+        let hangup = Arc::new(AtomicBool::new(false));
+        #[cfg(unix)]
+        if let Err(e) =
+            signal_hook::flag::register(signal_hook::consts::SIGHUP, Arc::clone(&hangup))
+        {
+            warn!("The configuration won't be reloaded on SIGHUP: {e}");
+        }
+
+        Self {
+            modified: config.file.as_deref().and_then(modified),
+            file: config.file.clone(),
+            hangup,
+        }
+    }
+
+    /// Whether the file changed or SIGHUP was received since the previous check.
+    pub fn is_requested(&mut self) -> bool {
+        let hangup = self.hangup.swap(false, Ordering::Relaxed);
+        if hangup {
+            info!("Hangup signal received, reloading the configuration");
+        }
+
+        let modified = self.file.as_deref().and_then(modified);
+        let changed = modified.is_some() && modified != self.modified;
+        if changed {
+            self.modified = modified;
+            info!("The configuration file changed, reloading it");
+        }
+
+        hangup || changed
+    }
+}
+
+fn modified(file: &Path) -> Option<SystemTime> {
+    std::fs::metadata(file)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use tempfile::tempdir;
+
+    #[test]
+    fn requests_reload_when_file_is_modified() {
+        let temp_dir = tempdir().unwrap();
+        let file = temp_dir.path().join("config.toml");
+        std::fs::write(&file, "port = 1234").unwrap();
+
+        let mut reloader = Reloader {
+            modified: modified(&file),
+            file: Some(file.clone()),
+            hangup: Arc::new(AtomicBool::new(false)),
+        };
+        assert!(!reloader.is_requested());
+
+        let later = SystemTime::now() + Duration::from_secs(10);
+        std::fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert!(reloader.is_requested());
+        assert!(!reloader.is_requested());
+
+        // A removed file keeps the current configuration until it's back.
+        std::fs::remove_file(&file).unwrap();
+        assert!(!reloader.is_requested());
+
+        reloader.hangup.store(true, Ordering::Relaxed);
+        assert!(reloader.is_requested());
+        assert!(!reloader.is_requested());
+    }
+}
@@ -2,12 +2,15 @@
 
 mod config;
 mod platform;
+mod queue;
+mod sink;
 mod watcher;
 
 use clap::Parser;
-use config::{Cli, Config};
-use platform::CrossMediaPlayer;
-use tokio::{signal, time};
+use config::{Cli, Command, Config, Reloader};
+use platform::{CrossMediaPlayer, MediaData};
+use std::sync::Arc;
+use tokio::{signal, sync::Notify, time};
 use watcher::Watcher;
 
 #[macro_use]
@@ -19,12 +22,36 @@ async fn main() -> anyhow::Result<()> {
     let verbosity = cli.verbosity.log_level().unwrap_or(log::Level::Error);
     simple_logger::init_with_level(verbosity).unwrap();
 
-    let config = Config::new(cli);
+    if cli.command == Some(Command::CheckConfig) {
+        return config::check_config(cli);
+    }
 
-    let media_player = platform::MediaPlayer::new();
+    let mut config = Config::new(cli);
+    if !config.errors.is_empty() || config.strict && !config.problems.is_empty() {
+        let mut problems = config.errors.clone();
+        problems.extend(config.problems.iter().cloned());
+        anyhow::bail!(
+            "Refusing to start with an invalid configuration:\n{}",
+            problems.join("\n")
+        );
+    }
+    for problem in &config.problems {
+        error!("{problem}");
+    }
+    config.save_migration();
+
+    let media_player: Box<dyn CrossMediaPlayer> = if config.mock_timeline.is_some() {
+        Box::new(platform::MockMediaPlayer::new(&config)?)
+    } else {
+        Box::new(platform::MediaPlayer::new(&config)?)
+    };
 
-    let watcher = Watcher::new(&config);
-    watcher.init().await?;
+    let mut watcher = Watcher::new(&config);
+    if config.dry_run {
+        info!("Dry run, the events are printed instead of sent");
+    } else {
+        watcher.init().await?;
+    }
 
     let ctrl_c = async {
         signal::ctrl_c()
@@ -43,30 +70,40 @@ async fn main() -> anyhow::Result<()> {
     #[cfg(not(unix))]
     let terminate = std::future::pending::<()>();
 
-    let run = async move {
+    let run = async {
         let mut interval = time::interval(config.poll_interval);
+        let mut reloader = Reloader::new(&config);
+        let changes = media_player.changes();
         let mut failed_attempts = 0;
         loop {
-            if !tick(failed_attempts, &mut interval).await {
+            if !tick(failed_attempts, &mut interval, changes.as_ref()).await && !config.is_queued()
+            {
                 return Err(anyhow::anyhow!("Maximum failed attempts reached"));
             }
-            let data = media_player.mediadata();
-            if let Some(data) = data {
-                if config.report_player(&data.player) {
-                    if let Err(e) = watcher.send_data(&data).await {
-                        error!("Failed to send data to the server: {}", e);
-                        failed_attempts += 1;
-                        continue;
-                    }
-                } else {
-                    trace!("Player \"{}\" is filtered out", data.player);
-                }
+            if reloader.is_requested() {
+                reload(&mut config, &mut watcher, &mut interval).await;
+            }
+            let mut failed = false;
+            if let Err(e) = watcher.flush().await {
+                error!("Failed to send queued data to the server: {e}");
+                failed = e.is_retryable();
+            }
+            for data in media_player.mediadata() {
+                failed |= report(&config, &mut watcher, data).await;
+            }
+            if let Err(e) = watcher.end_tick().await {
+                error!("Failed to send data to the server: {e}");
+                failed |= e.is_retryable();
+            }
+            if failed {
+                failed_attempts += 1;
+            } else {
+                failed_attempts = 0;
             }
-            failed_attempts = 0;
         }
     };
 
-    tokio::select! {
+    let result = tokio::select! {
         result = run => result,
         () = ctrl_c => {
             info!("Interruption signal received");
@@ -76,19 +113,78 @@ async fn main() -> anyhow::Result<()> {
             info!("Terminate signal received");
             Ok(())
         },
+    };
+    if let Err(e) = watcher.finish().await {
+        error!("Failed to report the current media: {e}");
+    }
+
+    result
+}
+
+/// Applies the reloaded configuration, or keeps the current one if the new one is invalid.
+async fn reload(config: &mut Config, watcher: &mut Watcher, interval: &mut time::Interval) {
+    let reloaded = match config.reload() {
+        Ok(reloaded) => reloaded,
+        Err(e) => {
+            error!("Keeping the current configuration: {e:#}");
+            return;
+        }
+    };
+
+    if let Err(e) = watcher.reconfigure(&reloaded, config).await {
+        error!("Failed to apply the reloaded configuration: {e:#}");
+    }
+    if reloaded.poll_interval != config.poll_interval {
+        *interval = time::interval(reloaded.poll_interval);
+    }
+    if reloaded.mpris_signals != config.mpris_signals {
+        warn!("Restart the watcher to apply the changed mpris_signals");
     }
+    *config = reloaded;
+    info!("The configuration is reloaded");
 }
 
-async fn tick(failed_attempts: u32, interval: &mut time::Interval) -> bool {
-    interval.tick().await;
+/// Reports the media as its player's policy tells, or explains why it's filtered out.
+/// Returns whether it failed in a way which retrying may fix.
+async fn report(config: &Config, watcher: &mut Watcher, mut data: MediaData) -> bool {
+    let policy = config.report_player(&data);
+    data.player.clone_from(&policy.player);
+    match config.filter_reason(&data, &policy) {
+        None => {
+            if let Err(e) = watcher.send_data(&data, &policy).await {
+                error!("Failed to send data to the server: {e}");
+                // Retrying faster won't help if the server rejects the data.
+                return e.is_retryable();
+            }
+        }
+        Some(reason) if config.dry_run => watcher.print_filtered(&data, &policy, &reason),
+        Some(reason) => trace!("Player \"{}\" is filtered out: {reason}", data.player),
+    }
+
+    false
+}
+
+/// Waits for the next poll or media change, backing off after failed attempts.
+/// Returns `false` once the attempts failed too many times in a row.
+async fn tick(
+    failed_attempts: u32,
+    interval: &mut time::Interval,
+    changes: Option<&Arc<Notify>>,
+) -> bool {
+    if let Some(changes) = changes {
+        tokio::select! {
+            _ = interval.tick() => {},
+            () = changes.notified() => trace!("Media change is reported"),
+        }
+    } else {
+        interval.tick().await;
+    }
 
     if failed_attempts == 0 {
         return true;
     }
-    if failed_attempts > 100 {
-        return false;
-    }
 
+    // The watcher keeps running after too many failures while it queues the reports, so it still backs off.
     let backoff = interval.period() * failed_attempts.min(10);
     warn!(
         "Backing off for {:?} ({} failed attempts)",
@@ -96,5 +192,80 @@ async fn tick(failed_attempts: u32, interval: &mut time::Interval) -> bool {
     );
     time::sleep(backoff).await;
 
-    true
+    failed_attempts <= 100
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::{json, Value};
+    use tempfile::tempdir;
+
+    const TIMELINE: &str = r#"
+[[media]]
+player = "Spotify"
+artists = ["The Gathering"]
+title = "Saturnine"
+
+[[media]]
+player = "VLC media player"
+title = "Home video"
+
+[[media]]
+player = "Firefox"
+title = "Meeting"
+site = "meet.example.com"
+"#;
+
+    #[tokio::test]
+    async fn mock_media_is_filtered_redacted_and_reported() {
+        let temp_dir = tempdir().unwrap();
+        let timeline = temp_dir.path().join("timeline.toml");
+        std::fs::write(&timeline, TIMELINE).unwrap();
+        let log = temp_dir.path().join("log.jsonl");
+        let config_file = temp_dir.path().join("config.toml");
+        let config_content = format!(
+            "exclude_players = [\"firefox\"]\n\n\
+             [[redactions]]\nfields = [\"title\"]\nmatch = {{ player = \"VLC\" }}\n\n\
+             [[sinks]]\ntype = \"file\"\npath = {:?}\n",
+            log.to_str().unwrap()
+        );
+        std::fs::write(&config_file, config_content).unwrap();
+
+        let config = Config::new(Cli::parse_from([
+            "aw-watcher-media-player".as_ref(),
+            "--config".as_ref(),
+            config_file.as_os_str(),
+            "--mock-timeline".as_ref(),
+            timeline.as_os_str(),
+        ]));
+        assert!(config.errors.is_empty(), "{:?}", config.errors);
+        let media_player = platform::MockMediaPlayer::new(&config).unwrap();
+        let mut watcher = Watcher::new(&config);
+        for data in media_player.mediadata() {
+            assert!(!report(&config, &mut watcher, data).await);
+        }
+        watcher.finish().await.unwrap();
+
+        let mut reported: Vec<(Value, Value)> = std::fs::read_to_string(&log)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let event: Value = serde_json::from_str(line).unwrap();
+                (
+                    event["data"]["player"].clone(),
+                    event["data"]["title"].clone(),
+                )
+            })
+            .collect();
+        reported.sort_by_key(|(player, _)| player.to_string());
+        assert_eq!(
+            reported,
+            [
+                (json!("Spotify"), json!("Saturnine")),
+                (json!("VLC media player"), json!("[redacted]")),
+            ]
+        );
+    }
 }
@@ -13,24 +13,183 @@ mod macos;
 #[cfg(target_os = "macos")]
 pub use macos::MediaPlayer;
 
+mod mock;
+pub use mock::MockMediaPlayer;
+
+use std::{sync::Arc, time::Duration};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
+use tokio::sync::Notify;
+
+use crate::config::{Config, Redaction};
 
 pub trait CrossMediaPlayer {
-    fn new() -> Self;
+    fn new(config: &Config) -> anyhow::Result<Self>
+    where
+        Self: Sized;
+
+    /// Returns the media of every player which is currently playing or paused.
+    fn mediadata(&self) -> Vec<MediaData>;
+
+    /// Notified whenever the media of any player changes,
+    /// if the platform is able to report changes without polling.
+    fn changes(&self) -> Option<Arc<Notify>> {
+        None
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaybackStatus {
+    #[default]
+    Playing,
+    Paused,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoopStatus {
+    None,
+    Track,
+    Playlist,
+}
+
+impl LoopStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            LoopStatus::None => "none",
+            LoopStatus::Track => "track",
+            LoopStatus::Playlist => "playlist",
+        }
+    }
+}
 
-    fn mediadata(&self) -> Option<MediaData>;
+/// Optional metadata which is reported only if it's allowed in the configuration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum ExtraField {
+    Genre,
+    TrackNumber,
+    DiscNumber,
+    Composer,
+    AlbumArtist,
+    TrackId,
+    ArtUrl,
+    UserRating,
+    ContentCreated,
 }
 
+impl ExtraField {
+    /// The key of the field in the reported data.
+    pub fn key(self) -> &'static str {
+        match self {
+            ExtraField::Genre => "genre",
+            ExtraField::TrackNumber => "track_number",
+            ExtraField::DiscNumber => "disc_number",
+            ExtraField::Composer => "composer",
+            ExtraField::AlbumArtist => "album_artist",
+            ExtraField::TrackId => "track_id",
+            ExtraField::ArtUrl => "art_url",
+            ExtraField::UserRating => "user_rating",
+            ExtraField::ContentCreated => "content_created",
+        }
+    }
+}
+
+#[derive(Clone, Default)]
 pub struct MediaData {
     artists: Option<Vec<String>>,
     album: Option<String>,
     title: Option<String>,
     uri: Option<String>,
+    length: Option<Duration>,
+    position: Option<Duration>,
+    rate: Option<f64>,
+    volume: Option<f64>,
+    shuffle: Option<bool>,
+    loop_status: Option<LoopStatus>,
+    genres: Option<Vec<String>>,
+    track_number: Option<i32>,
+    disc_number: Option<i32>,
+    composers: Option<Vec<String>>,
+    album_artists: Option<Vec<String>>,
+    track_id: Option<String>,
+    art_url: Option<String>,
+    user_rating: Option<f64>,
+    content_created: Option<String>,
+    /// Domain of the website playing the media in a browser.
+    site: Option<String>,
     pub player: String,
+    pub status: PlaybackStatus,
 }
 
 impl MediaData {
-    pub fn serialize(&self) -> Map<String, Value> {
+    /// Fields which change while the same media keeps playing.
+    /// They are not compared when deciding whether a heartbeat continues the previous event.
+    pub const VOLATILE_FIELDS: [&'static str; 6] = [
+        "length",
+        "position",
+        "rate",
+        "volume",
+        "shuffle",
+        "loop_status",
+    ];
+
+    /// Fields describing the media, which are reported whenever they are known.
+    pub const FIELDS: [&'static str; 6] = ["player", "artist", "album", "title", "uri", "site"];
+
+    /// Whether the key is a field of the serialized media, which the rules may refer to.
+    pub fn is_field(key: &str) -> bool {
+        Self::FIELDS.contains(&key)
+            || Self::VOLATILE_FIELDS.contains(&key)
+            || ExtraField::value_variants()
+                .iter()
+                .any(|field| field.key() == key)
+    }
+
+    pub fn site(&self) -> Option<&str> {
+        self.site.as_deref()
+    }
+
+    /// Whether the player plays with its volume turned all the way down.
+    pub fn is_muted(&self) -> bool {
+        self.volume.is_some_and(|volume| volume <= 0.0)
+    }
+
+    /// Time spent playing the track so far, according to the playback position and rate.
+    pub fn elapsed(&self) -> Duration {
+        let Some(position) = self.position else {
+            return Duration::ZERO;
+        };
+        match self.rate {
+            Some(rate) if rate > 0.0 => position.div_f64(rate),
+            _ => position,
+        }
+    }
+
+    /// Moves the playback position forward as if the media kept playing for `elapsed`.
+    pub fn advance(&mut self, elapsed: Duration) {
+        if self.status == PlaybackStatus::Paused {
+            return;
+        }
+        let Some(position) = self.position else {
+            return;
+        };
+        let mut position = position + elapsed.mul_f64(self.rate.unwrap_or(1.0).max(0.0));
+        if let Some(length) = self.length {
+            position = position.min(length);
+        }
+        self.position = Some(position);
+    }
+
+    /// Serializes the media with the allowed extra fields, redacted by the matching redactions.
+    pub fn serialize(
+        &self,
+        extra_fields: &[ExtraField],
+        redactions: &[Redaction],
+    ) -> Map<String, Value> {
         let mut data = Map::new();
 
         data.insert("player".to_string(), Value::String(self.player.clone()));
@@ -53,7 +212,217 @@ impl MediaData {
                 data.insert("uri".to_string(), Value::String(uri.clone()));
             }
         }
+        if let Some(site) = &self.site {
+            data.insert("site".to_string(), Value::String(site.clone()));
+        }
+        if let Some(length) = self.length {
+            data.insert("length".to_string(), Value::from(length.as_secs_f64()));
+        }
+        if let Some(position) = self.position {
+            data.insert("position".to_string(), Value::from(position.as_secs_f64()));
+        }
+        if let Some(rate) = self.rate {
+            data.insert("rate".to_string(), Value::from(rate));
+        }
+        if let Some(volume) = self.volume {
+            data.insert("volume".to_string(), Value::from(volume));
+        }
+        if let Some(shuffle) = self.shuffle {
+            data.insert("shuffle".to_string(), Value::Bool(shuffle));
+        }
+        if let Some(loop_status) = self.loop_status {
+            data.insert("loop_status".to_string(), Value::from(loop_status.as_str()));
+        }
+        for field in extra_fields {
+            if let Some(value) = self.extra_field(*field) {
+                data.insert(field.key().to_string(), value);
+            }
+        }
+        if !redactions.is_empty() {
+            // The redactions match any field, even if it's not reported.
+            let fields = self.serialize(ExtraField::value_variants(), &[]);
+            Redaction::apply_all(redactions, &fields, &mut data);
+        }
 
         data
     }
+
+    fn extra_field(&self, field: ExtraField) -> Option<Value> {
+        let list = |values: &Option<Vec<String>>| {
+            values
+                .as_ref()
+                .map(|values| values.join(", "))
+                .filter(|values| !values.is_empty())
+                .map(Value::String)
+        };
+        let text = |value: &Option<String>| {
+            value
+                .as_ref()
+                .filter(|value| !value.is_empty())
+                .map(|value| Value::String(value.clone()))
+        };
+
+        match field {
+            ExtraField::Genre => list(&self.genres),
+            ExtraField::TrackNumber => self.track_number.map(Value::from),
+            ExtraField::DiscNumber => self.disc_number.map(Value::from),
+            ExtraField::Composer => list(&self.composers),
+            ExtraField::AlbumArtist => list(&self.album_artists),
+            ExtraField::TrackId => text(&self.track_id),
+            ExtraField::ArtUrl => text(&self.art_url),
+            ExtraField::UserRating => self.user_rating.map(Value::from),
+            ExtraField::ContentCreated => text(&self.content_created),
+        }
+    }
+}
+
+#[cfg(test)]
+impl MediaData {
+    /// Media with the title played by the player, at the position in seconds.
+    pub fn track(player: &str, title: &str, position: u64, status: PlaybackStatus) -> Self {
+        Self {
+            title: Some(title.to_string()),
+            position: Some(Duration::from_secs(position)),
+            player: player.to_string(),
+            status,
+            ..Self::default()
+        }
+    }
+}
+
+/// Extracts the website domain from a web page URL, without the "www." prefix.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn site_from_url(url: &str) -> Option<String> {
+    let url = url::Url::parse(url).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+    let domain = url.host_str()?.to_lowercase();
+
+    Some(
+        domain
+            .strip_prefix("www.")
+            .map_or(domain.clone(), std::string::ToString::to_string),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elapsed_accounts_for_rate() {
+        let track = |rate| MediaData {
+            rate,
+            ..MediaData::track("Player", "Title", 50, PlaybackStatus::Playing)
+        };
+        assert_eq!(track(None).elapsed(), Duration::from_secs(50));
+        assert_eq!(track(Some(2.0)).elapsed(), Duration::from_secs(25));
+        assert_eq!(track(Some(0.0)).elapsed(), Duration::from_secs(50));
+        assert_eq!(MediaData::default().elapsed(), Duration::ZERO);
+    }
+
+    #[test]
+    fn advance_moves_position_up_to_length() {
+        let mut data = MediaData {
+            length: Some(Duration::from_secs(200)),
+            rate: Some(2.0),
+            ..MediaData::track("Player", "Title", 60, PlaybackStatus::Playing)
+        };
+        data.advance(Duration::from_secs(10));
+        assert_eq!(data.position, Some(Duration::from_secs(80)));
+
+        data.advance(Duration::from_secs(100));
+        assert_eq!(data.position, Some(Duration::from_secs(200)));
+
+        let mut paused = MediaData::track("Player", "Title", 50, PlaybackStatus::Paused);
+        paused.advance(Duration::from_secs(10));
+        assert_eq!(paused.position, Some(Duration::from_secs(50)));
+    }
+
+    #[test]
+    fn serialize_reports_timing_in_seconds() {
+        let data = MediaData {
+            length: Some(Duration::from_secs(200)),
+            rate: Some(1.5),
+            ..MediaData::track("Player", "Title", 60, PlaybackStatus::Playing)
+        }
+        .serialize(&[], &[]);
+
+        assert_eq!(data["length"], Value::from(200.0));
+        assert_eq!(data["position"], Value::from(60.0));
+        assert_eq!(data["rate"], Value::from(1.5));
+        assert!(MediaData::default()
+            .serialize(&[], &[])
+            .get("position")
+            .is_none());
+    }
+
+    #[test]
+    fn site_is_extracted_from_web_urls() {
+        assert_eq!(
+            site_from_url("https://www.youtube.com/watch?v=dQw4w9WgXcQ"),
+            Some("youtube.com".to_string())
+        );
+        assert_eq!(
+            site_from_url("https://SoundCloud.com/artist/track"),
+            Some("soundcloud.com".to_string())
+        );
+        assert_eq!(
+            site_from_url("http://music.example.org:8080/play"),
+            Some("music.example.org".to_string())
+        );
+        assert_eq!(site_from_url("file:///home/user/music/track.mp3"), None);
+        assert_eq!(site_from_url("not a url"), None);
+    }
+
+    #[test]
+    fn serialize_reports_playback_settings() {
+        let data = MediaData {
+            volume: Some(0.5),
+            shuffle: Some(true),
+            loop_status: Some(LoopStatus::Playlist),
+            ..MediaData::track("Player", "Title", 0, PlaybackStatus::Playing)
+        };
+        let serialized = data.serialize(&[], &[]);
+
+        assert_eq!(serialized["volume"], Value::from(0.5));
+        assert_eq!(serialized["shuffle"], Value::Bool(true));
+        assert_eq!(serialized["loop_status"], Value::from("playlist"));
+        assert!(!data.is_muted());
+        assert!(MediaData {
+            volume: Some(0.0),
+            ..data
+        }
+        .is_muted());
+        assert!(!MediaData::default().is_muted());
+    }
+
+    #[test]
+    fn serialize_reports_only_allowed_extra_fields() {
+        let data = MediaData {
+            genres: Some(vec!["Folk".to_string(), "Metal".to_string()]),
+            track_number: Some(3),
+            art_url: Some(String::new()),
+            ..MediaData::track("Player", "Title", 0, PlaybackStatus::Playing)
+        };
+
+        let serialized = data.serialize(&[], &[]);
+        assert!(serialized.get("genre").is_none());
+        assert!(serialized.get("track_number").is_none());
+
+        let serialized = data.serialize(
+            &[
+                ExtraField::Genre,
+                ExtraField::TrackNumber,
+                ExtraField::ArtUrl,
+                ExtraField::Composer,
+            ],
+            &[],
+        );
+        assert_eq!(serialized["genre"], Value::from("Folk, Metal"));
+        assert_eq!(serialized["track_number"], Value::from(3));
+        assert!(serialized.get("art_url").is_none());
+        assert!(serialized.get("composer").is_none());
+    }
 }
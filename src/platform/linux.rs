@@ -1,65 +1,403 @@
-use std::sync::mpsc;
+use std::collections::HashMap;
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 
-use mpris::{PlaybackStatus, PlayerFinder};
+use dbus::blocking::Connection;
+use dbus::message::MatchRule;
+use mpris::{FindingError, Metadata, MetadataValue, Player, PlayerFinder};
+use tokio::sync::Notify;
 
 use super::CrossMediaPlayer;
-use super::MediaData;
+use super::{site_from_url, LoopStatus, MediaData, PlaybackStatus};
+use crate::config::Config;
+
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+const MPRIS_BUS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+
+/// Time to wait before reconnecting to MPRIS after the first failure.
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Upper limit of the growing delay between reconnection attempts.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+/// Maximum time to wait for the helper thread to retrieve the media data.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub struct MediaPlayer {
-    sender: mpsc::Sender<()>,
-    receiver: mpsc::Receiver<Option<MediaData>>,
-    handler: thread::JoinHandle<()>,
+    mpris_signals: bool,
+    /// Shared by all helper threads so that the subscribers are kept across reconnections.
+    changes: Arc<Notify>,
+    worker: Mutex<Worker>,
+}
+
+/// The helper thread talking to MPRIS, restarted with a growing delay whenever it fails.
+struct Worker {
+    backend: Option<Backend>,
+    failures: u32,
+    retry_at: Instant,
+}
+
+enum Backend {
+    /// Requests the media data from a helper thread on every poll.
+    Polling {
+        sender: mpsc::Sender<()>,
+        receiver: mpsc::Receiver<Result<Vec<MediaData>, String>>,
+        handler: thread::JoinHandle<()>,
+    },
+    /// A helper thread keeps the media data up to date from D-Bus signals.
+    Signals {
+        state: Arc<Mutex<Vec<(MediaData, Instant)>>>,
+        handler: thread::JoinHandle<()>,
+    },
 }
 
 impl CrossMediaPlayer for MediaPlayer {
-    fn new() -> Self {
-        let (tx, rx) = mpsc::channel();
-        let (resp_tx, resp_rx) = mpsc::channel();
+    fn new(config: &Config) -> anyhow::Result<Self> {
+        let media_player = Self {
+            mpris_signals: config.mpris_signals,
+            changes: Arc::new(Notify::new()),
+            worker: Mutex::new(Worker {
+                backend: None,
+                failures: 0,
+                retry_at: Instant::now(),
+            }),
+        };
+        media_player
+            .lock_worker()
+            .connect(media_player.mpris_signals, &media_player.changes);
+
+        Ok(media_player)
+    }
 
-        let handler = thread::spawn(move || {
-            let player_finder = PlayerFinder::new().expect("MPRIS is unavailable");
+    fn mediadata(&self) -> Vec<MediaData> {
+        self.lock_worker()
+            .mediadata(self.mpris_signals, &self.changes)
+    }
 
-            while let Ok(()) = rx.recv() {
-                resp_tx
-                    .send(mediadata(&player_finder))
-                    .expect("Failed to send media data");
+    fn changes(&self) -> Option<Arc<Notify>> {
+        self.mpris_signals.then(|| Arc::clone(&self.changes))
+    }
+}
+
+impl MediaPlayer {
+    fn lock_worker(&self) -> std::sync::MutexGuard<'_, Worker> {
+        // The worker is left consistent even if a panic happened while it was locked.
+        self.worker
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl Worker {
+    /// Returns the media data, or no media while MPRIS is unavailable.
+    fn mediadata(&mut self, mpris_signals: bool, changes: &Arc<Notify>) -> Vec<MediaData> {
+        if self.backend.is_none() {
+            if Instant::now() < self.retry_at {
+                return Vec::new();
             }
-        });
+            self.connect(mpris_signals, changes);
+        }
+        let Some(backend) = &self.backend else {
+            return Vec::new();
+        };
 
-        Self {
-            sender: tx,
-            receiver: resp_rx,
-            handler,
+        match backend.mediadata() {
+            Ok(mediadata) => {
+                self.failures = 0;
+                mediadata
+            }
+            Err(e) => {
+                self.backend = None;
+                self.fail(&e);
+                Vec::new()
+            }
         }
     }
 
-    fn mediadata(&self) -> Option<MediaData> {
-        assert!(
-            !self.handler.is_finished(),
-            "The media data cannot be retrieved anymore"
-        );
+    fn connect(&mut self, mpris_signals: bool, changes: &Arc<Notify>) {
+        let backend = if mpris_signals {
+            spawn_signals(Arc::clone(changes)).or_else(|e| {
+                warn!("MPRIS signals are unavailable, falling back to polling: {e}");
+                spawn_polling()
+            })
+        } else {
+            spawn_polling()
+        };
+
+        match backend {
+            Ok(backend) => {
+                if self.failures > 0 {
+                    info!("Reconnected to MPRIS");
+                }
+                self.backend = Some(backend);
+            }
+            Err(e) => self.fail(&e),
+        }
+    }
 
-        self.sender.send(()).expect("Failed to request media data");
-        self.receiver.recv().expect("Failed to receive media data")
+    fn fail(&mut self, error: &str) {
+        let delay = MIN_RETRY_DELAY
+            .saturating_mul(2u32.saturating_pow(self.failures))
+            .min(MAX_RETRY_DELAY);
+        self.failures = self.failures.saturating_add(1);
+        self.retry_at = Instant::now() + delay;
+        error!("MPRIS is unavailable, reconnecting in {delay:?}: {error}");
     }
 }
 
-fn mediadata(player_finder: &PlayerFinder) -> Option<MediaData> {
-    let player = player_finder.find_active().ok()?;
+impl Backend {
+    fn mediadata(&self) -> Result<Vec<MediaData>, String> {
+        match self {
+            Backend::Polling {
+                sender,
+                receiver,
+                handler,
+            } => {
+                if handler.is_finished() {
+                    return Err("the MPRIS thread has stopped".to_string());
+                }
 
-    if player.get_playback_status().ok()? != PlaybackStatus::Playing {
-        trace!(
-            "Player {} is not playing with status {}",
-            player.bus_name(),
-            player
-                .get_playback_status()
-                .map(|status| format!("{status:?}"))
-                .unwrap_or("not found".to_string())
-        );
+                sender
+                    .send(())
+                    .map_err(|_| "the MPRIS thread has stopped".to_string())?;
+                receiver
+                    .recv_timeout(RESPONSE_TIMEOUT)
+                    .map_err(|e| format!("no response from the MPRIS thread: {e}"))?
+            }
+            Backend::Signals { state, handler } => {
+                if handler.is_finished() {
+                    return Err("the MPRIS signal listener has stopped".to_string());
+                }
+
+                let state = state
+                    .lock()
+                    .map_err(|_| "the MPRIS signal listener has panicked".to_string())?;
+                Ok(state
+                    .iter()
+                    .map(|(data, retrieved_at)| {
+                        let mut data = data.clone();
+                        data.advance(retrieved_at.elapsed());
+                        data
+                    })
+                    .collect())
+            }
+        }
+    }
+}
+
+fn spawn_polling() -> Result<Backend, String> {
+    let (tx, rx) = mpsc::channel();
+    let (resp_tx, resp_rx) = mpsc::channel();
+    let (ready_tx, ready_rx) = mpsc::channel();
+
+    let handler = thread::spawn(move || {
+        let player_finder = match PlayerFinder::new() {
+            Ok(player_finder) => {
+                ready_tx.send(Ok(())).ok();
+                player_finder
+            }
+            Err(e) => {
+                ready_tx.send(Err(e.to_string())).ok();
+                return;
+            }
+        };
+
+        while let Ok(()) = rx.recv() {
+            let mediadata = mediadata(&player_finder);
+            let disconnected = mediadata.is_err();
+            if resp_tx.send(mediadata).is_err() || disconnected {
+                return;
+            }
+        }
+    });
+
+    ready_rx
+        .recv()
+        .map_err(|_| "the MPRIS thread has stopped".to_string())??;
+
+    Ok(Backend::Polling {
+        sender: tx,
+        receiver: resp_rx,
+        handler,
+    })
+}
+
+fn spawn_signals(changes: Arc<Notify>) -> Result<Backend, String> {
+    let state = Arc::new(Mutex::new(Vec::new()));
+    let (ready_tx, ready_rx) = mpsc::channel();
 
-        return None;
+    let handler = {
+        let state = Arc::clone(&state);
+        thread::spawn(move || match SignalListener::new() {
+            Ok(listener) => {
+                ready_tx.send(Ok(())).ok();
+                if let Err(e) = listener.run(&state, &changes) {
+                    error!("Stopped listening to MPRIS signals: {e}");
+                }
+            }
+            Err(e) => {
+                ready_tx.send(Err(e.to_string())).ok();
+            }
+        })
+    };
+
+    ready_rx
+        .recv()
+        .map_err(|_| "the signal listener has stopped".to_string())??;
+
+    Ok(Backend::Signals { state, handler })
+}
+
+/// A change reported by D-Bus which may affect the media data.
+enum Change {
+    /// Properties or the position of the player with the given unique bus name have changed.
+    Properties(String),
+    /// An MPRIS player has appeared or disappeared.
+    Players,
+}
+
+struct SignalListener {
+    connection: Connection,
+    player_finder: PlayerFinder,
+    changes: mpsc::Receiver<Change>,
+    /// Known players and the cached media data of each of them with the time it was retrieved,
+    /// keyed by their unique bus name.
+    players: HashMap<String, (Player, Option<(MediaData, Instant)>)>,
+}
+
+impl SignalListener {
+    fn new() -> Result<Self, Box<dyn std::error::Error>> {
+        let connection = Connection::new_session()?;
+        let (tx, rx) = mpsc::channel();
+
+        for rule in [
+            MatchRule::new_signal("org.freedesktop.DBus.Properties", "PropertiesChanged"),
+            MatchRule::new_signal("org.mpris.MediaPlayer2.Player", "Seeked"),
+        ] {
+            let properties_tx = tx.clone();
+            connection.add_match(rule.with_path(MPRIS_PATH), move |(): (), _, message| {
+                if let Some(sender) = message.sender() {
+                    properties_tx
+                        .send(Change::Properties(sender.to_string()))
+                        .ok();
+                }
+                true
+            })?;
+        }
+        connection.add_match(
+            MatchRule::new_signal("org.freedesktop.DBus", "NameOwnerChanged"),
+            move |(name, _, _): (String, String, String), _, _| {
+                if name.starts_with(MPRIS_BUS_PREFIX) {
+                    tx.send(Change::Players).ok();
+                }
+                true
+            },
+        )?;
+
+        Ok(Self {
+            connection,
+            player_finder: PlayerFinder::new()?,
+            changes: rx,
+            players: HashMap::new(),
+        })
+    }
+
+    /// Keeps the media data up to date until the connection to D-Bus is lost.
+    fn run(
+        mut self,
+        state: &Mutex<Vec<(MediaData, Instant)>>,
+        notify: &Notify,
+    ) -> Result<(), String> {
+        self.rescan()?;
+        self.publish(state, notify);
+
+        loop {
+            self.connection
+                .process(Duration::from_secs(1))
+                .map_err(|e| e.to_string())?;
+
+            let mut changed = false;
+            while let Ok(change) = self.changes.try_recv() {
+                match change {
+                    Change::Properties(unique_name) => {
+                        if let Some((player, data)) = self.players.get_mut(&unique_name) {
+                            *data = player_mediadata(player).map(|data| (data, Instant::now()));
+                        } else {
+                            self.rescan()?;
+                        }
+                    }
+                    Change::Players => self.rescan()?,
+                }
+                changed = true;
+            }
+
+            if changed {
+                self.publish(state, notify);
+            }
+        }
+    }
+
+    fn rescan(&mut self) -> Result<(), String> {
+        let players = find_all(&self.player_finder)?;
+
+        self.players = players
+            .into_iter()
+            .map(|player| {
+                let data = player_mediadata(&player).map(|data| (data, Instant::now()));
+                (player.unique_name().to_string(), (player, data))
+            })
+            .collect();
+
+        Ok(())
+    }
+
+    fn publish(&self, state: &Mutex<Vec<(MediaData, Instant)>>, notify: &Notify) {
+        let mediadata = self
+            .players
+            .values()
+            .filter_map(|(_, data)| data.clone())
+            .collect();
+        *state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = mediadata;
+        notify.notify_one();
     }
+}
+
+fn mediadata(player_finder: &PlayerFinder) -> Result<Vec<MediaData>, String> {
+    Ok(find_all(player_finder)?
+        .iter()
+        .filter_map(player_mediadata)
+        .collect())
+}
+
+/// Finds all MPRIS players, failing only if D-Bus itself is unavailable.
+fn find_all(player_finder: &PlayerFinder) -> Result<Vec<Player>, String> {
+    match player_finder.find_all() {
+        Ok(players) => Ok(players),
+        Err(FindingError::NoPlayerFound) => {
+            trace!("No MPRIS players found");
+            Ok(Vec::new())
+        }
+        Err(FindingError::DBusError(e)) => Err(e.to_string()),
+    }
+}
+
+fn player_mediadata(player: &Player) -> Option<MediaData> {
+    let status = match player.get_playback_status() {
+        Ok(mpris::PlaybackStatus::Playing) => PlaybackStatus::Playing,
+        Ok(mpris::PlaybackStatus::Paused) => PlaybackStatus::Paused,
+        status => {
+            trace!(
+                "Player {} is not playing with status {}",
+                player.bus_name(),
+                status
+                    .map(|status| format!("{status:?}"))
+                    .unwrap_or("not found".to_string())
+            );
+
+            return None;
+        }
+    };
 
     let metadata = if let Ok(metadata) = player.get_metadata() {
         Some(metadata)
@@ -71,11 +409,39 @@ fn mediadata(player_finder: &PlayerFinder) -> Option<MediaData> {
         None
     }?;
 
+    let track_id = metadata.track_id().map(|track_id| track_id.to_string());
+    // Browsers report the page URL, some players put it into the track ID instead.
+    let site = metadata
+        .url()
+        .and_then(site_from_url)
+        .or_else(|| track_id.as_deref().and_then(site_from_url));
+    let album_artists = metadata.album_artists().map(|artists| {
+        artists
+            .iter()
+            .map(std::string::ToString::to_string)
+            .collect::<Vec<_>>()
+    });
+
     Some(MediaData {
         player: player.identity().to_string(),
+        status,
         album: metadata.album_name().map(std::string::ToString::to_string),
         title: metadata.title().map(std::string::ToString::to_string),
         uri: metadata.url().map(std::string::ToString::to_string),
+        length: metadata.length(),
+        position: player.get_position().ok(),
+        rate: player.get_playback_rate().ok(),
+        volume: player.get_volume().ok(),
+        shuffle: player.checked_get_shuffle().ok().flatten(),
+        loop_status: player
+            .checked_get_loop_status()
+            .ok()
+            .flatten()
+            .map(|loop_status| match loop_status {
+                mpris::LoopStatus::None => LoopStatus::None,
+                mpris::LoopStatus::Track => LoopStatus::Track,
+                mpris::LoopStatus::Playlist => LoopStatus::Playlist,
+            }),
         artists: if let Some(artists) = metadata.artists() {
             Some(
                 artists
@@ -84,12 +450,34 @@ fn mediadata(player_finder: &PlayerFinder) -> Option<MediaData> {
                     .collect(),
             )
         } else {
-            metadata.album_artists().map(|artists| {
-                artists
-                    .iter()
-                    .map(std::string::ToString::to_string)
-                    .collect()
-            })
+            album_artists.clone()
         },
+        genres: string_list(&metadata, "xesam:genre"),
+        track_number: metadata.track_number(),
+        disc_number: metadata.disc_number(),
+        composers: string_list(&metadata, "xesam:composer"),
+        album_artists,
+        track_id,
+        site,
+        art_url: metadata.art_url().map(std::string::ToString::to_string),
+        user_rating: metadata
+            .get("xesam:userRating")
+            .and_then(MetadataValue::as_f64),
+        content_created: metadata
+            .get("xesam:contentCreated")
+            .and_then(MetadataValue::as_str)
+            .map(std::string::ToString::to_string),
     })
 }
+
+fn string_list(metadata: &Metadata, key: &str) -> Option<Vec<String>> {
+    metadata
+        .get(key)
+        .and_then(MetadataValue::as_str_array)
+        .map(|values| {
+            values
+                .iter()
+                .map(std::string::ToString::to_string)
+                .collect()
+        })
+}
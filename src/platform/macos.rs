@@ -1,4 +1,7 @@
-use super::{CrossMediaPlayer, MediaData};
+use std::time::Duration;
+
+use super::{CrossMediaPlayer, MediaData, PlaybackStatus};
+use crate::config::Config;
 use media_remote::NowPlayingPerl;
 
 pub struct MediaPlayer {
@@ -6,26 +9,41 @@ pub struct MediaPlayer {
 }
 
 impl CrossMediaPlayer for MediaPlayer {
-    fn new() -> Self {
-        Self {
+    fn new(_config: &Config) -> anyhow::Result<Self> {
+        Ok(Self {
             now_playing: NowPlayingPerl::new(),
-        }
+        })
     }
 
-    fn mediadata(&self) -> Option<MediaData> {
+    fn mediadata(&self) -> Vec<MediaData> {
         let guard = self.now_playing.get_info();
-        let info = guard.as_ref()?;
+        let Some(info) = guard.as_ref() else {
+            return Vec::new();
+        };
 
-        if !info.is_playing.unwrap_or(false) {
-            return None;
-        }
+        let status = if info.is_playing.unwrap_or(false) {
+            PlaybackStatus::Playing
+        } else if info.title.is_some() {
+            PlaybackStatus::Paused
+        } else {
+            return Vec::new();
+        };
 
-        Some(MediaData {
+        vec![MediaData {
             title: info.title.clone(),
             artists: info.artist.clone().map(|artist| vec![artist]),
             album: info.album.clone(),
             player: info.bundle_name.clone().unwrap_or_default(),
             uri: None,
-        })
+            length: info
+                .duration
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok()),
+            position: info
+                .elapsed_time
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok()),
+            rate: None,
+            status,
+            ..MediaData::default()
+        }]
     }
 }
@@ -0,0 +1,222 @@
+use std::{path::Path, time::Duration, time::Instant};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use super::{CrossMediaPlayer, MediaData, PlaybackStatus};
+use crate::config::Config;
+
+/// Media which a player shows for a while since the start of the timeline.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    /// Seconds since the start when the media appears.
+    #[serde(default)]
+    from: f64,
+    /// Seconds since the start when the media disappears, it stays until the exit if not specified.
+    to: Option<f64>,
+    player: String,
+    #[serde(default)]
+    status: PlaybackStatus,
+    artists: Option<Vec<String>>,
+    album: Option<String>,
+    title: Option<String>,
+    uri: Option<String>,
+    site: Option<String>,
+    /// Track length in seconds.
+    length: Option<f64>,
+    /// Playback position in seconds when the media appears, which moves forward while it's playing.
+    position: Option<f64>,
+    rate: Option<f64>,
+    volume: Option<f64>,
+}
+
+impl Entry {
+    fn media_at(&self, elapsed: f64) -> MediaData {
+        let seconds =
+            |secs: Option<f64>| secs.and_then(|secs| Duration::try_from_secs_f64(secs).ok());
+
+        let mut data = MediaData {
+            artists: self.artists.clone(),
+            album: self.album.clone(),
+            title: self.title.clone(),
+            uri: self.uri.clone(),
+            length: seconds(self.length),
+            position: seconds(self.position),
+            rate: self.rate,
+            volume: self.volume,
+            site: self.site.clone(),
+            player: self.player.clone(),
+            status: self.status,
+            ..MediaData::default()
+        };
+        data.advance(seconds(Some(elapsed - self.from)).unwrap_or_default());
+
+        data
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Timeline {
+    media: Vec<Entry>,
+}
+
+impl Timeline {
+    /// Reads the timeline from a JSON file if it has the ".json" extension, or from a TOML file otherwise.
+    fn read(file: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(file)
+            .with_context(|| format!("Failed to read {}", file.display()))?;
+        let is_json = file
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+
+        if is_json {
+            serde_json::from_str(&content).context("Failed to parse the JSON timeline")
+        } else {
+            toml::from_str(&content).context("Failed to parse the TOML timeline")
+        }
+    }
+
+    /// Returns the media shown by the players at the time since the start of the timeline.
+    fn media_at(&self, elapsed: f64) -> Vec<MediaData> {
+        self.media
+            .iter()
+            .filter(|entry| entry.from <= elapsed && entry.to.is_none_or(|to| elapsed < to))
+            .map(|entry| entry.media_at(elapsed))
+            .collect()
+    }
+}
+
+/// Plays the media from a scripted timeline instead of real players, e.g. to test the watcher without them.
+pub struct MockMediaPlayer {
+    timeline: Timeline,
+    started_at: Instant,
+}
+
+impl CrossMediaPlayer for MockMediaPlayer {
+    fn new(config: &Config) -> anyhow::Result<Self> {
+        let timeline = match &config.mock_timeline {
+            Some(file) => Timeline::read(file)
+                .with_context(|| format!("Invalid mock timeline {}", file.display()))?,
+            None => anyhow::bail!("There is no mock timeline to play"),
+        };
+        info!(
+            "Playing {} mock media instead of the real players",
+            timeline.media.len()
+        );
+
+        Ok(Self {
+            timeline,
+            started_at: Instant::now(),
+        })
+    }
+
+    fn mediadata(&self) -> Vec<MediaData> {
+        self.timeline
+            .media_at(self.started_at.elapsed().as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::Value;
+    use tempfile::tempdir;
+
+    const TIMELINE: &str = r#"
+[[media]]
+to = 30
+player = "Spotify"
+artists = ["The Gathering"]
+title = "A"
+length = 200
+position = 0
+
+[[media]]
+from = 30
+to = 40
+player = "Spotify"
+status = "paused"
+artists = ["The Gathering"]
+title = "A"
+length = 200
+position = 30
+
+[[media]]
+from = 40
+player = "Firefox"
+title = "B"
+site = "youtube.com"
+"#;
+
+    fn summary(media: &[MediaData]) -> Vec<(String, PlaybackStatus, Value, Option<f64>)> {
+        media
+            .iter()
+            .map(|data| {
+                let serialized = data.serialize(&[], &[]);
+                (
+                    data.player.clone(),
+                    data.status,
+                    serialized["title"].clone(),
+                    serialized.get("position").and_then(Value::as_f64),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn plays_media_from_timeline() {
+        let temp_dir = tempdir().unwrap();
+        let file = temp_dir.path().join("timeline.toml");
+        std::fs::write(&file, TIMELINE).unwrap();
+        let timeline = Timeline::read(&file).unwrap();
+
+        assert_eq!(
+            summary(&timeline.media_at(10.0)),
+            vec![(
+                "Spotify".to_string(),
+                PlaybackStatus::Playing,
+                Value::from("A"),
+                Some(10.0)
+            )]
+        );
+        assert_eq!(
+            summary(&timeline.media_at(35.0)),
+            vec![(
+                "Spotify".to_string(),
+                PlaybackStatus::Paused,
+                Value::from("A"),
+                Some(30.0)
+            )]
+        );
+        assert_eq!(
+            summary(&timeline.media_at(1000.0)),
+            vec![(
+                "Firefox".to_string(),
+                PlaybackStatus::Playing,
+                Value::from("B"),
+                None
+            )]
+        );
+        assert_eq!(timeline.media_at(1000.0)[0].site(), Some("youtube.com"));
+    }
+
+    #[test]
+    fn reads_json_timeline_and_rejects_unknown_fields() {
+        let temp_dir = tempdir().unwrap();
+        let file = temp_dir.path().join("timeline.json");
+        std::fs::write(
+            &file,
+            r#"{"media": [{"player": "VLC", "title": "C", "to": 5}]}"#,
+        )
+        .unwrap();
+        let timeline = Timeline::read(&file).unwrap();
+        assert_eq!(timeline.media_at(1.0).len(), 1);
+        assert!(timeline.media_at(5.0).is_empty());
+
+        std::fs::write(&file, r#"{"media": [{"player": "VLC", "titel": "C"}]}"#).unwrap();
+        assert!(Timeline::read(&file).is_err());
+    }
+}
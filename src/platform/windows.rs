@@ -1,43 +1,104 @@
-use super::{CrossMediaPlayer, MediaData};
+use std::time::Duration;
 
+use super::{CrossMediaPlayer, LoopStatus, MediaData, PlaybackStatus};
+use crate::config::Config;
+
+use windows::Foundation::TimeSpan;
 use windows::Media::Control::{
-    GlobalSystemMediaTransportControlsSessionManager,
+    GlobalSystemMediaTransportControlsSession, GlobalSystemMediaTransportControlsSessionManager,
     GlobalSystemMediaTransportControlsSessionPlaybackStatus,
 };
+use windows::Media::MediaPlaybackAutoRepeatMode;
 
 pub struct MediaPlayer {}
 
 impl CrossMediaPlayer for MediaPlayer {
-    fn new() -> Self {
-        Self {}
+    fn new(_config: &Config) -> anyhow::Result<Self> {
+        Ok(Self {})
     }
 
-    fn mediadata(&self) -> Option<MediaData> {
+    fn mediadata(&self) -> Vec<MediaData> {
         let session_manager = GlobalSystemMediaTransportControlsSessionManager::RequestAsync()
             .expect("Failed to request media session manager")
             .get()
             .expect("Failed to get media session manager");
 
-        let session = session_manager.GetCurrentSession().ok()?;
+        let Ok(sessions) = session_manager.GetSessions() else {
+            return Vec::new();
+        };
 
-        let status = session.GetPlaybackInfo().ok()?.PlaybackStatus().ok()?;
-        if status != GlobalSystemMediaTransportControlsSessionPlaybackStatus::Playing {
-            return None;
-        }
+        sessions
+            .into_iter()
+            .filter_map(|session| session_mediadata(&session))
+            .collect()
+    }
+}
 
-        let properties = session.TryGetMediaPropertiesAsync().ok()?.get().ok()?;
+fn session_mediadata(session: &GlobalSystemMediaTransportControlsSession) -> Option<MediaData> {
+    let playback_info = session.GetPlaybackInfo().ok()?;
+    let status = match playback_info.PlaybackStatus().ok()? {
+        GlobalSystemMediaTransportControlsSessionPlaybackStatus::Playing => PlaybackStatus::Playing,
+        GlobalSystemMediaTransportControlsSessionPlaybackStatus::Paused => PlaybackStatus::Paused,
+        _ => return None,
+    };
 
-        let title = properties.Title().ok().map(|s| s.to_string());
-        let artists = properties.Artist().ok().map(|s| vec![s.to_string()]);
-        let album = properties.AlbumTitle().ok().map(|s| s.to_string());
-        let player = session.SourceAppUserModelId().ok()?.to_string();
+    let properties = session.TryGetMediaPropertiesAsync().ok()?.get().ok()?;
 
-        Some(MediaData {
-            artists,
-            album,
-            title,
-            uri: None,
-            player,
-        })
-    }
+    let title = properties.Title().ok().map(|s| s.to_string());
+    let artists = properties.Artist().ok().map(|s| vec![s.to_string()]);
+    let album = properties.AlbumTitle().ok().map(|s| s.to_string());
+    let player = session.SourceAppUserModelId().ok()?.to_string();
+    let timeline = session.GetTimelineProperties().ok();
+    let rate = playback_info
+        .PlaybackRate()
+        .and_then(|rate| rate.Value())
+        .ok();
+    let shuffle = playback_info
+        .IsShuffleActive()
+        .and_then(|shuffle| shuffle.Value())
+        .ok();
+    let loop_status = playback_info
+        .AutoRepeatMode()
+        .and_then(|mode| mode.Value())
+        .ok()
+        .and_then(|mode| match mode {
+            MediaPlaybackAutoRepeatMode::None => Some(LoopStatus::None),
+            MediaPlaybackAutoRepeatMode::Track => Some(LoopStatus::Track),
+            MediaPlaybackAutoRepeatMode::List => Some(LoopStatus::Playlist),
+            _ => None,
+        });
+
+    Some(MediaData {
+        artists,
+        album,
+        title,
+        uri: None,
+        length: timeline
+            .as_ref()
+            .and_then(|timeline| timeline.EndTime().ok())
+            .and_then(time_span),
+        position: timeline
+            .as_ref()
+            .and_then(|timeline| timeline.Position().ok())
+            .and_then(time_span),
+        rate,
+        shuffle,
+        loop_status,
+        genres: properties
+            .Genres()
+            .ok()
+            .map(|genres| genres.into_iter().map(|genre| genre.to_string()).collect()),
+        track_number: properties.TrackNumber().ok().filter(|number| *number > 0),
+        album_artists: properties.AlbumArtist().ok().map(|s| vec![s.to_string()]),
+        player,
+        status,
+        ..MediaData::default()
+    })
+}
+
+fn time_span(span: TimeSpan) -> Option<Duration> {
+    // TimeSpan is measured in 100-nanosecond ticks.
+    u64::try_from(span.Duration)
+        .ok()
+        .map(|ticks| Duration::from_nanos(ticks.saturating_mul(100)))
 }
@@ -0,0 +1,367 @@
+use std::{
+    collections::VecDeque,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::sink::{Report, SinkFuture};
+use crate::watcher::WatcherError;
+
+/// A report which can wait in the queue.
+pub trait QueueEntry: Serialize + DeserializeOwned {
+    /// What the entry is called in the log, e.g. "heartbeat".
+    const KIND: &'static str;
+
+    /// Extends this entry by the next one if they can be sent as one.
+    fn merge(&mut self, _next: &Self) -> bool {
+        false
+    }
+}
+
+impl QueueEntry for Report {
+    const KIND: &'static str = "heartbeat";
+
+    fn merge(&mut self, next: &Self) -> bool {
+        Report::merge(self, next)
+    }
+}
+
+/// Where the queued reports are sent to, such as a server.
+pub trait Deliver<T> {
+    fn deliver<'a>(&'a mut self, entry: &'a T) -> SinkFuture<'a, Result<(), WatcherError>>;
+}
+
+/// Reports waiting for the server to become reachable, persisted as JSON lines.
+pub struct Queue<T: QueueEntry = Report> {
+    file: PathBuf,
+    entries: VecDeque<T>,
+    max_size: usize,
+}
+
+impl<T: QueueEntry> Queue<T> {
+    /// Opens the queue file with the given name in the data directory.
+    pub fn open_default(name: &str, max_size: usize) -> anyhow::Result<Self> {
+        let data_dir = dirs::data_local_dir().context("Impossible to find data directory")?;
+        let app_dir = data_dir.join(env!("CARGO_PKG_NAME"));
+        fs::create_dir_all(&app_dir)
+            .with_context(|| format!("Failed to create data directory {}", app_dir.display()))?;
+
+        Self::open(&app_dir.join(name), max_size)
+    }
+
+    /// Opens the queue stored in the file, loading the reports left from the previous runs.
+    pub fn open(file: &Path, max_size: usize) -> anyhow::Result<Self> {
+        let mut entries = VecDeque::new();
+        if file.exists() {
+            let content = fs::read_to_string(file)
+                .with_context(|| format!("Failed to read queue file {}", file.display()))?;
+            for line in content.lines().filter(|line| !line.trim().is_empty()) {
+                match serde_json::from_str(line) {
+                    Ok(entry) => entries.push_back(entry),
+                    Err(e) => warn!(
+                        "Skipping malformed queued {} in {}: {e}",
+                        T::KIND,
+                        file.display()
+                    ),
+                }
+            }
+        }
+        if !entries.is_empty() {
+            info!(
+                "{} queued {}s in {} will be sent in order",
+                entries.len(),
+                T::KIND,
+                file.display()
+            );
+        }
+
+        let mut queue = Self {
+            file: file.to_path_buf(),
+            entries,
+            max_size,
+        };
+        if queue.entries.len() > max_size {
+            queue.truncate();
+            queue.persist()?;
+        }
+
+        Ok(queue)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.entries.iter()
+    }
+
+    /// Adds the report to the end of the queue, dropping the oldest ones above the size limit.
+    pub fn push(&mut self, report: T) -> anyhow::Result<()> {
+        if let Some(last) = self.entries.back_mut() {
+            if last.merge(&report) {
+                return self.persist();
+            }
+        }
+
+        self.entries.push_back(report);
+        if self.entries.len() > self.max_size {
+            self.truncate();
+            return self.persist();
+        }
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.file)
+            .with_context(|| format!("Failed to open queue file {}", self.file.display()))?;
+        let line = serde_json::to_string(self.entries.back().expect("Queue can't be empty"))?;
+        writeln!(file, "{line}")
+            .with_context(|| format!("Failed to write queue file {}", self.file.display()))
+    }
+
+    /// Removes the given number of the oldest reports after they were sent.
+    pub fn remove(&mut self, count: usize) -> anyhow::Result<()> {
+        self.entries.drain(..count.min(self.entries.len()));
+        self.persist()
+    }
+
+    /// Sends the entry, or queues it if the destination is unreachable or older entries are waiting.
+    pub async fn send(
+        &mut self,
+        entry: T,
+        destination: &mut impl Deliver<T>,
+    ) -> Result<(), WatcherError> {
+        if self.is_empty() {
+            let Err(e) = destination.deliver(&entry).await else {
+                return Ok(());
+            };
+            if !e.is_retryable() {
+                return Err(e);
+            }
+            warn!("Queueing the {} until it can be sent: {e}", T::KIND);
+            return self.push(entry).map_err(WatcherError::Queue);
+        }
+
+        self.push(entry).map_err(WatcherError::Queue)?;
+        if let Err(e) = self.flush(destination).await {
+            debug!("Queued {}s are not sent yet: {e}", T::KIND);
+        }
+
+        Ok(())
+    }
+
+    /// Sends the entries which failed to be sent before, in the order they were created.
+    /// Entries rejected by the destination are dropped, as they would never be accepted.
+    pub async fn flush(&mut self, destination: &mut impl Deliver<T>) -> Result<(), WatcherError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let total = self.len();
+        let mut sent = 0;
+        let mut dropped = 0;
+        let mut result = Ok(());
+        for entry in self.iter() {
+            match destination.deliver(entry).await {
+                Ok(()) => sent += 1,
+                Err(e) if e.is_retryable() => {
+                    warn!(
+                        "Failed to send queued {}s, {} are left",
+                        T::KIND,
+                        total - sent - dropped
+                    );
+                    result = Err(e);
+                    break;
+                }
+                Err(e) => {
+                    error!("Dropping the queued {}: {e}", T::KIND);
+                    dropped += 1;
+                }
+            }
+        }
+        if sent + dropped > 0 {
+            if dropped > 0 {
+                info!(
+                    "Sent {sent} and dropped {dropped} of {total} queued {}s",
+                    T::KIND
+                );
+            } else {
+                info!("Sent {sent} of {total} queued {}s", T::KIND);
+            }
+            self.remove(sent + dropped).map_err(WatcherError::Queue)?;
+        }
+
+        result
+    }
+
+    fn truncate(&mut self) {
+        let excess = self.entries.len().saturating_sub(self.max_size);
+        if excess > 0 {
+            warn!("Queue is full, dropping {excess} oldest {}s", T::KIND);
+            self.entries.drain(..excess);
+        }
+    }
+
+    fn persist(&self) -> anyhow::Result<()> {
+        let mut content = String::new();
+        for entry in self.iter() {
+            content.push_str(&serde_json::to_string(entry)?);
+            content.push('\n');
+        }
+
+        fs::write(&self.file, content)
+            .with_context(|| format!("Failed to write queue file {}", self.file.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    use crate::sink::test_report::report;
+
+    fn heartbeat(title: &str, start_secs: i64, duration_secs: i64) -> Report {
+        report(
+            json!({ "player": "Spotify", "title": title }),
+            start_secs,
+            duration_secs,
+            Some(6.0),
+        )
+    }
+
+    #[test]
+    fn merges_continuing_heartbeats() {
+        let temp_dir = tempdir().unwrap();
+        let mut queue = Queue::<Report>::open(&temp_dir.path().join("queue.jsonl"), 10).unwrap();
+
+        queue.push(heartbeat("A", 0, 0)).unwrap();
+        queue.push(heartbeat("A", 0, 5)).unwrap();
+        queue.push(heartbeat("A", 5, 5)).unwrap();
+        queue.push(heartbeat("B", 10, 0)).unwrap();
+        queue.push(heartbeat("A", 30, 0)).unwrap();
+
+        assert_eq!(queue.len(), 3);
+        assert_eq!(
+            queue.iter().next().unwrap().event.duration,
+            chrono::Duration::seconds(10)
+        );
+    }
+
+    #[test]
+    fn persists_heartbeats_in_order() {
+        let temp_dir = tempdir().unwrap();
+        let file = temp_dir.path().join("queue.jsonl");
+
+        let mut queue = Queue::<Report>::open(&file, 10).unwrap();
+        queue.push(heartbeat("A", 0, 0)).unwrap();
+        queue.push(heartbeat("B", 10, 0)).unwrap();
+        queue.push(heartbeat("C", 20, 0)).unwrap();
+        queue.remove(1).unwrap();
+
+        let queue = Queue::<Report>::open(&file, 10).unwrap();
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.iter().next().unwrap().event.data["title"], json!("B"));
+    }
+
+    #[test]
+    fn drops_oldest_heartbeats_above_limit() {
+        let temp_dir = tempdir().unwrap();
+        let file = temp_dir.path().join("queue.jsonl");
+
+        let mut queue = Queue::<Report>::open(&file, 2).unwrap();
+        queue.push(heartbeat("A", 0, 0)).unwrap();
+        queue.push(heartbeat("B", 10, 0)).unwrap();
+        queue.push(heartbeat("C", 20, 0)).unwrap();
+
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.iter().next().unwrap().event.data["title"], json!("B"));
+
+        let queue = Queue::<Report>::open(&file, 1).unwrap();
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.iter().next().unwrap().event.data["title"], json!("C"));
+    }
+
+    /// Fails with the given errors before accepting the heartbeats, recording the accepted titles.
+    #[derive(Default)]
+    struct Destination {
+        errors: VecDeque<WatcherError>,
+        accepted: Vec<String>,
+    }
+
+    impl Deliver<Report> for Destination {
+        fn deliver<'a>(
+            &'a mut self,
+            report: &'a Report,
+        ) -> SinkFuture<'a, Result<(), WatcherError>> {
+            Box::pin(async move {
+                if let Some(error) = self.errors.pop_front() {
+                    return Err(error);
+                }
+                self.accepted
+                    .push(report.event.data["title"].as_str().unwrap().to_string());
+                Ok(())
+            })
+        }
+    }
+
+    fn unavailable() -> WatcherError {
+        WatcherError::Scrobbler {
+            code: 16,
+            message: "Service temporarily unavailable".to_string(),
+        }
+    }
+
+    fn rejected() -> WatcherError {
+        WatcherError::Scrobbler {
+            code: 6,
+            message: "Invalid parameters".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_queued_heartbeats_in_order() {
+        let temp_dir = tempdir().unwrap();
+        let mut queue = Queue::<Report>::open(&temp_dir.path().join("queue.jsonl"), 10).unwrap();
+        let mut destination = Destination::default();
+
+        destination.errors.push_back(unavailable());
+        queue
+            .send(heartbeat("A", 0, 0), &mut destination)
+            .await
+            .unwrap();
+        destination.errors.push_back(unavailable());
+        queue
+            .send(heartbeat("B", 10, 0), &mut destination)
+            .await
+            .unwrap();
+        assert_eq!(queue.len(), 2);
+        assert!(destination.accepted.is_empty());
+
+        destination.errors.push_back(rejected());
+        queue
+            .send(heartbeat("C", 20, 0), &mut destination)
+            .await
+            .unwrap();
+        assert!(queue.is_empty());
+        assert_eq!(destination.accepted, ["B", "C"]);
+
+        destination.errors.push_back(rejected());
+        let Err(error) = queue.send(heartbeat("D", 30, 0), &mut destination).await else {
+            panic!("Rejected heartbeat is not queued");
+        };
+        assert!(!error.is_retryable());
+        assert!(queue.is_empty());
+    }
+}
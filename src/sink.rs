@@ -0,0 +1,113 @@
+mod activitywatch;
+mod audioscrobbler;
+mod dry_run;
+mod file;
+mod listenbrainz;
+mod scrobble;
+#[cfg(test)]
+pub mod test_report;
+#[cfg(test)]
+mod test_server;
+
+use std::{future::Future, pin::Pin};
+
+pub use activitywatch::ActivityWatchSink;
+pub use audioscrobbler::{Audioscrobbler, AudioscrobblerSinkConfig};
+pub use dry_run::{print_json, DryRunSink};
+#[cfg(test)]
+pub use file::FileFormat;
+pub use file::{FileSink, FileSinkConfig};
+pub use listenbrainz::{ListenBrainz, ListenBrainzSinkConfig};
+pub use scrobble::ScrobbleSink;
+
+use aw_client_rust::Event as AwEvent;
+use serde::{Deserialize, Serialize};
+
+use crate::watcher::WatcherError;
+
+pub type SinkFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A heartbeat or a complete event to report to a bucket.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Report {
+    pub bucket: String,
+    /// Type to create the bucket with if it doesn't exist yet.
+    pub bucket_type: String,
+    pub event: AwEvent,
+    /// `None` for complete events, which are inserted as is.
+    pub pulsetime: Option<f64>,
+}
+
+impl Report {
+    /// Extends this heartbeat by the next one the same way the server would merge them.
+    pub fn merge(&mut self, next: &Report) -> bool {
+        let (Some(_), Some(pulsetime)) = (self.pulsetime, next.pulsetime) else {
+            return false;
+        };
+        let end = self.end();
+        let pulsetime = pulsetime_duration(pulsetime);
+        if self.bucket != next.bucket
+            || self.event.data != next.event.data
+            || next.event.timestamp < self.event.timestamp
+            || next.event.timestamp > end + pulsetime
+        {
+            return false;
+        }
+
+        self.event.duration = next.end().max(end) - self.event.timestamp;
+        true
+    }
+
+    pub fn end(&self) -> chrono::DateTime<chrono::Utc> {
+        self.event.timestamp + self.event.duration
+    }
+
+    /// The latest time the next heartbeat may start to be merged with this one.
+    pub fn mergeable_until(&self) -> chrono::DateTime<chrono::Utc> {
+        self.end() + pulsetime_duration(self.pulsetime.unwrap_or_default())
+    }
+}
+
+fn pulsetime_duration(pulsetime: f64) -> chrono::Duration {
+    std::time::Duration::try_from_secs_f64(pulsetime)
+        .ok()
+        .and_then(|pulsetime| chrono::Duration::from_std(pulsetime).ok())
+        .unwrap_or_default()
+}
+
+/// A destination of the reported media.
+pub trait Sink: Send {
+    /// Prepares the sink for the reports to the known buckets.
+    fn init<'a>(
+        &'a mut self,
+        buckets: &'a [(String, &'static str)],
+    ) -> SinkFuture<'a, anyhow::Result<()>>;
+
+    /// Reports the media, succeeding if it's either delivered or going to be delivered later.
+    fn send<'a>(&'a mut self, report: &'a Report) -> SinkFuture<'a, Result<(), WatcherError>>;
+
+    /// Delivers the reports which were postponed before, called on every poll.
+    fn flush(&mut self) -> SinkFuture<'_, Result<(), WatcherError>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Delivers everything which is left before exiting.
+    fn close(&mut self) -> SinkFuture<'_, Result<(), WatcherError>> {
+        self.flush()
+    }
+}
+
+/// A sink in the configuration, several of them may be used at once.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkConfig {
+    /// ActivityWatch server at the configured host and port.
+    #[serde(rename = "activitywatch")]
+    ActivityWatch,
+    File(FileSinkConfig),
+    /// ListenBrainz or a compatible service, receiving the played tracks.
+    #[serde(rename = "listenbrainz")]
+    ListenBrainz(ListenBrainzSinkConfig),
+    /// Last.fm or another service with the Audioscrobbler 2.0 API, receiving the played tracks.
+    Audioscrobbler(AudioscrobblerSinkConfig),
+}
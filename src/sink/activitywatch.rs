@@ -0,0 +1,152 @@
+use std::{collections::HashSet, time::Duration};
+
+use anyhow::Context;
+use aw_client_rust::AwClient;
+
+use super::{Report, Sink, SinkFuture};
+use crate::config::Config;
+use crate::queue::{Deliver, Queue};
+use crate::watcher::WatcherError;
+
+const CLIENT_NAME: &str = env!("CARGO_PKG_NAME");
+
+/// Reports to the ActivityWatch server, queueing the reports while it's unreachable.
+pub struct ActivityWatchSink {
+    server: Server,
+    /// Heartbeats which failed to be sent, replayed in order once the server is reachable.
+    queue: Option<Queue>,
+}
+
+impl ActivityWatchSink {
+    pub fn new(config: &Config) -> Self {
+        let queue = if config.queue_size > 0 {
+            Queue::open_default("queue.jsonl", config.queue_size)
+                .map_err(|e| error!("Heartbeats won't be queued while offline: {e:?}"))
+                .ok()
+        } else {
+            None
+        };
+
+        Self {
+            server: Server {
+                client: AwClient::new(&config.host, &config.port.to_string(), CLIENT_NAME),
+                created_buckets: HashSet::new(),
+            },
+            queue,
+        }
+    }
+
+    async fn create_bucket(
+        &self,
+        bucket_name: &str,
+        bucket_type: &str,
+    ) -> Result<(), WatcherError> {
+        let mut delay = Duration::from_secs(1);
+        let mut attempts = 0;
+        loop {
+            let f = self
+                .server
+                .client
+                .create_bucket_simple(bucket_name, bucket_type);
+            match f.await {
+                Ok(val) => return Ok(val),
+                Err(e) => {
+                    let e = WatcherError::from_client(e, bucket_name);
+                    if attempts >= 3 || !e.is_retryable() {
+                        return Err(e);
+                    }
+                    warn!("Failed to create bucket, retrying in {delay:?}: {e}");
+
+                    attempts += 1;
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+            }
+        }
+    }
+
+    /// Sends the report, or queues it if the server is unreachable or older reports are waiting.
+    async fn send_report(&mut self, report: &Report) -> Result<(), WatcherError> {
+        match &mut self.queue {
+            Some(queue) => queue.send(report.clone(), &mut self.server).await,
+            None => self.server.deliver(report).await,
+        }
+    }
+
+    async fn flush_queue(&mut self) -> Result<(), WatcherError> {
+        match &mut self.queue {
+            Some(queue) => queue.flush(&mut self.server).await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Sink for ActivityWatchSink {
+    fn init<'a>(
+        &'a mut self,
+        buckets: &'a [(String, &'static str)],
+    ) -> SinkFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            for (bucket_name, bucket_type) in buckets {
+                match self.create_bucket(bucket_name, bucket_type).await {
+                    Ok(()) => {
+                        self.server.created_buckets.insert(bucket_name.clone());
+                    }
+                    // The heartbeats are queued and the bucket is created once the server is reachable.
+                    Err(e) if self.queue.is_some() && e.is_retryable() => {
+                        warn!("Bucket {bucket_name} will be created once the server is reachable: {e}");
+                    }
+                    Err(e) => {
+                        return Err(e).context(format!("Failed to create bucket {bucket_name}"));
+                    }
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    fn send<'a>(&'a mut self, report: &'a Report) -> SinkFuture<'a, Result<(), WatcherError>> {
+        Box::pin(self.send_report(report))
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_, Result<(), WatcherError>> {
+        Box::pin(self.flush_queue())
+    }
+}
+
+/// The ActivityWatch server, which the heartbeats are delivered to.
+struct Server {
+    client: AwClient,
+    /// Buckets which are known to exist on the server.
+    created_buckets: HashSet<String>,
+}
+
+impl Deliver<Report> for Server {
+    /// Sends the heartbeat or inserts the complete event, creating its bucket first if it's not known to exist.
+    fn deliver<'a>(&'a mut self, report: &'a Report) -> SinkFuture<'a, Result<(), WatcherError>> {
+        Box::pin(async move {
+            if !self.created_buckets.contains(&report.bucket) {
+                self.client
+                    .create_bucket_simple(&report.bucket, &report.bucket_type)
+                    .await
+                    .map_err(|e| WatcherError::from_client(e, &report.bucket))?;
+                self.created_buckets.insert(report.bucket.clone());
+            }
+
+            match report.pulsetime {
+                Some(pulsetime) => {
+                    self.client
+                        .heartbeat(&report.bucket, &report.event, pulsetime)
+                        .await
+                }
+                None => {
+                    self.client
+                        .insert_event(&report.bucket, &report.event)
+                        .await
+                }
+            }
+            .map_err(|e| WatcherError::from_client(e, &report.bucket))
+        })
+    }
+}
@@ -0,0 +1,238 @@
+use std::{collections::BTreeMap, fmt::Write, time::Duration};
+
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::scrobble::{self, Scrobble, ScrobbleApi, Track};
+use super::SinkFuture;
+use crate::watcher::WatcherError;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn default_url() -> String {
+    String::from("https://ws.audioscrobbler.com/2.0/")
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AudioscrobblerSinkConfig {
+    /// Endpoint of an Audioscrobbler 2.0 API, such as Last.fm or a self-hosted server.
+    #[serde(default = "default_url")]
+    pub url: String,
+    pub api_key: String,
+    pub api_secret: String,
+    /// Key of the user's session, authorized for the API key.
+    pub session_key: String,
+}
+
+/// Last.fm or another service with the Audioscrobbler 2.0 API.
+pub struct Audioscrobbler {
+    client: reqwest::Client,
+    config: AudioscrobblerSinkConfig,
+}
+
+type Params = BTreeMap<&'static str, String>;
+
+impl Audioscrobbler {
+    pub fn new(config: &AudioscrobblerSinkConfig) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            config: config.clone(),
+        }
+    }
+
+    /// Calls the signed write method with the parameters.
+    async fn call(&self, method: &str, mut params: Params) -> Result<(), WatcherError> {
+        params.insert("method", method.to_string());
+        params.insert("api_key", self.config.api_key.clone());
+        params.insert("sk", self.config.session_key.clone());
+        let signature = sign(&params, &self.config.api_secret);
+        params.insert("api_sig", signature);
+        params.insert("format", String::from("json"));
+
+        let response = self
+            .client
+            .post(&self.config.url)
+            .form(&params)
+            .send()
+            .await
+            .map_err(WatcherError::from_request)?;
+
+        // The API reports errors in the body, even with a successful status.
+        let status_error = response.error_for_status_ref().err();
+        let body: Option<Value> = response.json().await.ok();
+        if let Some(code) = body.as_ref().and_then(|body| body.get("error")) {
+            return Err(WatcherError::Scrobbler {
+                code: code.as_u64().unwrap_or_default(),
+                message: body
+                    .as_ref()
+                    .and_then(|body| body.get("message"))
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string(),
+            });
+        }
+
+        status_error.map_or(Ok(()), |e| Err(WatcherError::from_request(e)))
+    }
+}
+
+impl ScrobbleApi for Audioscrobbler {
+    fn queue_file(&self) -> String {
+        scrobble::queue_file("audioscrobbler", &self.config.url)
+    }
+
+    fn now_playing<'a>(&'a self, track: &'a Track) -> SinkFuture<'a, Result<(), WatcherError>> {
+        Box::pin(self.call("track.updateNowPlaying", track_params(track)))
+    }
+
+    fn scrobble<'a>(&'a self, scrobble: &'a Scrobble) -> SinkFuture<'a, Result<(), WatcherError>> {
+        let mut params = track_params(&scrobble.track);
+        params.insert("timestamp", scrobble.started_at.timestamp().to_string());
+
+        Box::pin(self.call("track.scrobble", params))
+    }
+}
+
+fn track_params(track: &Track) -> Params {
+    let mut params = Params::new();
+    params.insert("artist", track.artist.clone());
+    params.insert("track", track.title.clone());
+    if let Some(album) = &track.album {
+        params.insert("album", album.clone());
+    }
+    if let Some(length) = track.length {
+        params.insert("duration", length.as_secs().to_string());
+    }
+
+    params
+}
+
+/// Signs the request: the MD5 digest of the parameters sorted by name, concatenated with the secret.
+fn sign(params: &Params, secret: &str) -> String {
+    let mut text = String::new();
+    for (name, value) in params {
+        text.push_str(name);
+        text.push_str(value);
+    }
+    text.push_str(secret);
+
+    let mut signature = String::with_capacity(32);
+    for byte in Md5::digest(text.as_bytes()) {
+        let _ = write!(signature, "{byte:02x}");
+    }
+    signature
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use crate::sink::scrobble::ScrobbleSink;
+    use crate::sink::test_report::{self, at};
+    use crate::sink::test_server::{Request, TestServer};
+    use crate::sink::{Report, Sink};
+
+    fn api(server: &TestServer) -> Audioscrobbler {
+        Audioscrobbler::new(&AudioscrobblerSinkConfig {
+            url: format!("{}/2.0/", server.url),
+            api_key: "key".to_string(),
+            api_secret: "secret".to_string(),
+            session_key: "session".to_string(),
+        })
+    }
+
+    fn form(request: &Request) -> HashMap<String, String> {
+        url::form_urlencoded::parse(request.body.as_bytes())
+            .into_owned()
+            .collect()
+    }
+
+    fn report(duration_secs: i64) -> Report {
+        test_report::report(
+            json!({
+                "player": "Spotify",
+                "artist": "Anathema",
+                "title": "Untouchable, Part 1",
+                "album": "Weather Systems",
+                "length": 375.0,
+            }),
+            0,
+            duration_secs,
+            Some(6.0),
+        )
+    }
+
+    #[tokio::test]
+    async fn signs_now_playing_and_scrobble() {
+        let server = TestServer::start(&[]).await;
+        let mut sink = ScrobbleSink::with_queue(api(&server), None);
+
+        sink.send(&report(10)).await.unwrap();
+        sink.send(&report(190)).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].path, "/2.0/");
+
+        let now_playing = form(&requests[0]);
+        assert_eq!(now_playing["method"], "track.updateNowPlaying");
+        assert_eq!(now_playing["format"], "json");
+        // MD5 of "albumWeather Systemsapi_keykeyartistAnathemaduration375methodtrack.updateNowPlaying
+        // sksessiontrackUntouchable, Part 1secret".
+        assert_eq!(now_playing["api_sig"], "e336ac53b7a76f3b7cb8af6be1b83f9a");
+
+        let scrobble = form(&requests[1]);
+        assert_eq!(scrobble["method"], "track.scrobble");
+        assert_eq!(scrobble["timestamp"], "1700000000");
+        assert_eq!(scrobble["track"], "Untouchable, Part 1");
+        // MD5 of "albumWeather Systemsapi_keykeyartistAnathemaduration375methodtrack.scrobble
+        // sksessiontimestamp1700000000trackUntouchable, Part 1secret".
+        assert_eq!(scrobble["api_sig"], "b830c37807d9f073e622e66f15ea6433");
+    }
+
+    #[tokio::test]
+    async fn errors_in_body_are_classified() {
+        let server = TestServer::start(&[
+            (
+                200,
+                r#"{"error": 16, "message": "Temporarily unavailable"}"#,
+            ),
+            (403, r#"{"error": 9, "message": "Invalid session key"}"#),
+            (503, "Service Unavailable"),
+        ])
+        .await;
+        let api = api(&server);
+        let scrobble = Scrobble {
+            track: Track {
+                artist: "Anathema".to_string(),
+                title: "Lightning Song".to_string(),
+                album: None,
+                length: None,
+                player: "Spotify".to_string(),
+            },
+            started_at: at(0),
+        };
+
+        let error = api.scrobble(&scrobble).await.unwrap_err();
+        assert!(matches!(error, WatcherError::Scrobbler { code: 16, .. }));
+        assert!(error.is_retryable());
+
+        let error = api.scrobble(&scrobble).await.unwrap_err();
+        assert!(matches!(error, WatcherError::Scrobbler { code: 9, .. }));
+        assert!(!error.is_retryable());
+
+        let error = api.scrobble(&scrobble).await.unwrap_err();
+        assert!(matches!(error, WatcherError::Server { .. }));
+        assert!(error.is_retryable());
+
+        api.scrobble(&scrobble).await.unwrap();
+    }
+}
@@ -0,0 +1,31 @@
+use serde_json::json;
+
+use super::{Report, Sink, SinkFuture};
+use crate::watcher::WatcherError;
+
+/// Prints the reports as JSON instead of sending them, to try the configuration.
+pub struct DryRunSink;
+
+impl Sink for DryRunSink {
+    fn init<'a>(
+        &'a mut self,
+        _buckets: &'a [(String, &'static str)],
+    ) -> SinkFuture<'a, anyhow::Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn send<'a>(&'a mut self, report: &'a Report) -> SinkFuture<'a, Result<(), WatcherError>> {
+        Box::pin(async move {
+            print_json(&json!({ "filtered": false, "report": report }));
+            Ok(())
+        })
+    }
+}
+
+/// Prints the value as pretty JSON to the standard output.
+pub fn print_json(value: &serde_json::Value) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{json}"),
+        Err(e) => error!("Failed to print the event: {e}"),
+    }
+}
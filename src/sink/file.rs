@@ -0,0 +1,312 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::{Report, Sink, SinkFuture};
+use crate::watcher::WatcherError;
+
+/// Columns of the CSV file, the other data is reported only to JSON lines.
+const CSV_COLUMNS: [&str; 8] = [
+    "player", "artist", "album", "title", "uri", "site", "length", "position",
+];
+
+fn default_max_size() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_max_files() -> usize {
+    5
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileFormat {
+    #[default]
+    Jsonl,
+    Csv,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FileSinkConfig {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub format: FileFormat,
+    /// Size in bytes after which the file is rotated.
+    #[serde(default = "default_max_size")]
+    pub max_size: u64,
+    /// Number of rotated files to keep, e.g. "listening.jsonl.1".
+    #[serde(default = "default_max_files")]
+    pub max_files: usize,
+}
+
+/// Writes a local log of the played media, one complete event per line.
+pub struct FileSink {
+    config: FileSinkConfig,
+    /// The last heartbeat of every player in every bucket, written once the next one can't be merged into it.
+    pending: HashMap<(String, String), Report>,
+}
+
+impl FileSink {
+    pub fn new(config: FileSinkConfig) -> Self {
+        Self {
+            config,
+            pending: HashMap::new(),
+        }
+    }
+
+    fn send_report(&mut self, report: &Report) -> Result<(), WatcherError> {
+        if report.pulsetime.is_none() {
+            return self.write(report);
+        }
+
+        let player = report.event.data.get("player").and_then(Value::as_str);
+        let key = (
+            report.bucket.clone(),
+            player.unwrap_or_default().to_string(),
+        );
+        if let Some(pending) = self.pending.get_mut(&key) {
+            if pending.merge(report) {
+                return Ok(());
+            }
+        }
+        match self.pending.insert(key, report.clone()) {
+            Some(previous) => self.write(&previous),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the pending heartbeats which can't be merged with the next ones anymore, or all of them.
+    fn write_pending(&mut self, all: bool) -> Result<(), WatcherError> {
+        let now = Utc::now();
+        let mut finished: Vec<Report> = Vec::new();
+        self.pending.retain(|_, report| {
+            if all || report.mergeable_until() < now {
+                finished.push(report.clone());
+                return false;
+            }
+            true
+        });
+        finished.sort_by_key(|report| report.event.timestamp);
+
+        for report in &finished {
+            self.write(report)?;
+        }
+
+        Ok(())
+    }
+
+    fn write(&self, report: &Report) -> Result<(), WatcherError> {
+        let path = &self.config.path;
+        let file_error = |error| WatcherError::File {
+            path: path.display().to_string(),
+            error,
+        };
+
+        let line = match self.config.format {
+            FileFormat::Jsonl => json!({
+                "bucket": report.bucket,
+                "timestamp": report.event.timestamp,
+                "duration": duration_secs(report),
+                "data": report.event.data,
+            })
+            .to_string(),
+            FileFormat::Csv => csv_line(report),
+        };
+
+        let size = fs::metadata(path)
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+        if size > 0 && size + line.len() as u64 >= self.config.max_size {
+            rotate(path, self.config.max_files).map_err(file_error)?;
+        }
+        let is_new = fs::metadata(path).map_or(true, |metadata| metadata.len() == 0);
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(file_error)?;
+        if is_new && self.config.format == FileFormat::Csv {
+            writeln!(file, "{}", csv_header()).map_err(file_error)?;
+        }
+        writeln!(file, "{line}").map_err(file_error)
+    }
+}
+
+impl Sink for FileSink {
+    fn init<'a>(
+        &'a mut self,
+        _buckets: &'a [(String, &'static str)],
+    ) -> SinkFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            if let Some(dir) = self.config.path.parent() {
+                if !dir.as_os_str().is_empty() {
+                    fs::create_dir_all(dir)?;
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    fn send<'a>(&'a mut self, report: &'a Report) -> SinkFuture<'a, Result<(), WatcherError>> {
+        Box::pin(async move { self.send_report(report) })
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_, Result<(), WatcherError>> {
+        Box::pin(async move { self.write_pending(false) })
+    }
+
+    fn close(&mut self) -> SinkFuture<'_, Result<(), WatcherError>> {
+        Box::pin(async move { self.write_pending(true) })
+    }
+}
+
+fn duration_secs(report: &Report) -> f64 {
+    report
+        .event
+        .duration
+        .to_std()
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+fn csv_header() -> String {
+    let mut columns = vec!["timestamp", "duration", "bucket"];
+    columns.extend(CSV_COLUMNS);
+    columns.join(",")
+}
+
+fn csv_line(report: &Report) -> String {
+    let mut fields = vec![
+        report.event.timestamp.to_rfc3339(),
+        duration_secs(report).to_string(),
+        report.bucket.clone(),
+    ];
+    for column in CSV_COLUMNS {
+        fields.push(match report.event.data.get(column) {
+            Some(Value::String(value)) => value.clone(),
+            Some(value) => value.to_string(),
+            None => String::new(),
+        });
+    }
+
+    fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Renames the file to "<file>.1", shifting the older rotated files and removing the oldest one.
+fn rotate(path: &Path, max_files: usize) -> std::io::Result<()> {
+    let rotated = |index: usize| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(format!(".{index}"));
+        PathBuf::from(name)
+    };
+
+    if max_files == 0 {
+        return fs::remove_file(path);
+    }
+    let oldest = rotated(max_files);
+    if oldest.exists() {
+        fs::remove_file(oldest)?;
+    }
+    for index in (1..max_files).rev() {
+        let file = rotated(index);
+        if file.exists() {
+            fs::rename(file, rotated(index + 1))?;
+        }
+    }
+
+    fs::rename(path, rotated(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    use crate::sink::test_report;
+
+    fn report(title: &str, start_secs: i64, duration_secs: i64, pulsetime: Option<f64>) -> Report {
+        test_report::report(
+            json!({ "player": "Spotify", "title": title }),
+            start_secs,
+            duration_secs,
+            pulsetime,
+        )
+    }
+
+    fn sink(path: &Path, format: FileFormat, max_size: u64) -> FileSink {
+        FileSink::new(FileSinkConfig {
+            path: path.to_path_buf(),
+            format,
+            max_size,
+            max_files: 1,
+        })
+    }
+
+    #[tokio::test]
+    async fn writes_merged_heartbeats_as_json_lines() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("log.jsonl");
+        let mut sink = sink(&path, FileFormat::Jsonl, default_max_size());
+
+        sink.send(&report("A", 0, 0, Some(6.0))).await.unwrap();
+        sink.send(&report("A", 0, 5, Some(6.0))).await.unwrap();
+        sink.send(&report("B", 5, 0, Some(6.0))).await.unwrap();
+        sink.send(&report("C", 20, 10, None)).await.unwrap();
+        sink.close().await.unwrap();
+
+        let lines: Vec<Value> = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["data"]["title"], json!("A"));
+        assert_eq!(lines[0]["duration"], json!(5.0));
+        assert_eq!(lines[1]["data"]["title"], json!("C"));
+        assert_eq!(lines[2]["data"]["title"], json!("B"));
+    }
+
+    #[tokio::test]
+    async fn writes_csv_with_header_and_rotates() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("log.csv");
+        let mut sink = sink(&path, FileFormat::Csv, 150);
+
+        sink.send(&report("Hello, \"World\"", 0, 10, None))
+            .await
+            .unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            content.lines().collect::<Vec<_>>(),
+            vec![
+                "timestamp,duration,bucket,player,artist,album,title,uri,site,length,position",
+                "2023-11-14T22:13:20+00:00,10,bucket,Spotify,,,\"Hello, \"\"World\"\"\",,,,",
+            ]
+        );
+
+        sink.send(&report("B", 10, 10, None)).await.unwrap();
+        let rotated = temp_dir.path().join("log.csv.1");
+        assert_eq!(fs::read_to_string(rotated).unwrap(), content);
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+    }
+}
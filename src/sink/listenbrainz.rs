@@ -0,0 +1,210 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::scrobble::{self, Scrobble, ScrobbleApi, Track};
+use super::SinkFuture;
+use crate::watcher::WatcherError;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn default_url() -> String {
+    String::from("https://api.listenbrainz.org")
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ListenBrainzSinkConfig {
+    /// Root of a ListenBrainz-compatible API.
+    #[serde(default = "default_url")]
+    pub url: String,
+    pub token: String,
+}
+
+/// ListenBrainz or a compatible service, which receives the tracks as listens.
+pub struct ListenBrainz {
+    client: reqwest::Client,
+    url: String,
+    token: String,
+}
+
+impl ListenBrainz {
+    pub fn new(config: &ListenBrainzSinkConfig) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            url: config.url.clone(),
+            token: config.token.clone(),
+        }
+    }
+
+    async fn submit(&self, listen_type: &str, payload: Value) -> Result<(), WatcherError> {
+        self.client
+            .post(format!(
+                "{}/1/submit-listens",
+                self.url.trim_end_matches('/')
+            ))
+            .header("Authorization", format!("Token {}", self.token))
+            .json(&json!({ "listen_type": listen_type, "payload": payload }))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map(|_| ())
+            .map_err(WatcherError::from_request)
+    }
+}
+
+impl ScrobbleApi for ListenBrainz {
+    fn queue_file(&self) -> String {
+        scrobble::queue_file("listenbrainz", &self.url)
+    }
+
+    fn now_playing<'a>(&'a self, track: &'a Track) -> SinkFuture<'a, Result<(), WatcherError>> {
+        Box::pin(self.submit(
+            "playing_now",
+            json!([{ "track_metadata": track_metadata(track) }]),
+        ))
+    }
+
+    fn scrobble<'a>(&'a self, scrobble: &'a Scrobble) -> SinkFuture<'a, Result<(), WatcherError>> {
+        Box::pin(self.submit(
+            "single",
+            json!([{
+                "listened_at": scrobble.started_at.timestamp(),
+                "track_metadata": track_metadata(&scrobble.track),
+            }]),
+        ))
+    }
+}
+
+fn track_metadata(track: &Track) -> Value {
+    let mut additional_info = json!({
+        "media_player": track.player,
+        "submission_client": env!("CARGO_PKG_NAME"),
+        "submission_client_version": env!("CARGO_PKG_VERSION"),
+    });
+    if let Some(length) = track.length {
+        additional_info["duration_ms"] =
+            Value::from(u64::try_from(length.as_millis()).unwrap_or(u64::MAX));
+    }
+
+    let mut metadata = json!({
+        "artist_name": track.artist,
+        "track_name": track.title,
+        "additional_info": additional_info,
+    });
+    if let Some(album) = &track.album {
+        metadata["release_name"] = Value::from(album.as_str());
+    }
+
+    metadata
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    use crate::queue::Queue;
+    use crate::sink::scrobble::ScrobbleSink;
+    use crate::sink::test_report::{self, at};
+    use crate::sink::test_server::{Request, TestServer};
+    use crate::sink::{Report, Sink};
+
+    fn report(length: Option<f64>, duration_secs: i64, pulsetime: Option<f64>) -> Report {
+        test_report::report(
+            json!({
+                "player": "Spotify",
+                "artist": "The Gathering",
+                "title": "My Electricity",
+                "album": "How to Measure a Planet?",
+                "length": length,
+            }),
+            0,
+            duration_secs,
+            pulsetime,
+        )
+    }
+
+    fn api(server: &TestServer) -> ListenBrainz {
+        ListenBrainz::new(&ListenBrainzSinkConfig {
+            url: server.url.clone(),
+            token: "secret".to_string(),
+        })
+    }
+
+    fn body(request: &Request) -> Value {
+        serde_json::from_str(&request.body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn submits_playing_now_and_listen_after_half_length() {
+        let server = TestServer::start(&[]).await;
+        let mut sink = ScrobbleSink::with_queue(api(&server), None);
+
+        sink.send(&report(Some(100.0), 10, Some(6.0)))
+            .await
+            .unwrap();
+        sink.send(&report(Some(100.0), 49, Some(6.0)))
+            .await
+            .unwrap();
+        assert_eq!(server.requests().len(), 1);
+        sink.send(&report(Some(100.0), 50, Some(6.0)))
+            .await
+            .unwrap();
+        sink.send(&report(Some(100.0), 60, Some(6.0)))
+            .await
+            .unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].path, "/1/submit-listens");
+        assert_eq!(requests[0].headers["authorization"], "Token secret");
+
+        let playing_now = body(&requests[0]);
+        assert_eq!(playing_now["listen_type"], json!("playing_now"));
+        assert!(playing_now["payload"][0].get("listened_at").is_none());
+
+        let single = body(&requests[1]);
+        assert_eq!(single["listen_type"], json!("single"));
+        let listen = &single["payload"][0];
+        assert_eq!(listen["listened_at"], json!(at(0).timestamp()));
+        assert_eq!(
+            listen["track_metadata"]["track_name"],
+            json!("My Electricity")
+        );
+        assert_eq!(
+            listen["track_metadata"]["release_name"],
+            json!("How to Measure a Planet?")
+        );
+        assert_eq!(
+            listen["track_metadata"]["additional_info"]["duration_ms"],
+            json!(100_000)
+        );
+    }
+
+    #[tokio::test]
+    async fn queues_failed_listens_until_server_is_back() {
+        let server = TestServer::start(&[(503, "")]).await;
+        let temp_dir = tempdir().unwrap();
+        let queue = Queue::open(&temp_dir.path().join("listens.jsonl"), 10).unwrap();
+        let mut sink = ScrobbleSink::with_queue(api(&server), Some(queue));
+
+        // Too short for a track without the length.
+        sink.send(&report(None, 200, None)).await.unwrap();
+        assert!(server.requests().is_empty());
+
+        sink.send(&report(None, 240, None)).await.unwrap();
+        assert_eq!(server.requests().len(), 1);
+
+        sink.flush().await.unwrap();
+        sink.flush().await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(body(&requests[0]), body(&requests[1]));
+    }
+}
@@ -0,0 +1,302 @@
+use std::{collections::HashMap, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::{Report, Sink, SinkFuture};
+use crate::queue::{Deliver, Queue, QueueEntry};
+use crate::watcher::WatcherError;
+
+/// A track is scrobbled once it played for half of its length, but at most for this time.
+const MAX_SCROBBLE_THRESHOLD: Duration = Duration::from_mins(4);
+
+/// A track which can be scrobbled, it needs at least the artist and the title.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Track {
+    pub artist: String,
+    pub title: String,
+    pub album: Option<String>,
+    pub length: Option<Duration>,
+    pub player: String,
+}
+
+impl Track {
+    /// Reads the track from the reported data, if it has the artist and the title.
+    fn from_data(data: &Map<String, Value>) -> Option<Self> {
+        let text = |key| data.get(key).and_then(Value::as_str).map(str::to_string);
+
+        Some(Self {
+            artist: text("artist")?,
+            title: text("title")?,
+            album: text("album"),
+            length: data
+                .get("length")
+                .and_then(Value::as_f64)
+                .and_then(|length| Duration::try_from_secs_f64(length).ok()),
+            player: text("player").unwrap_or_default(),
+        })
+    }
+
+    fn is_same(&self, other: &Track) -> bool {
+        self.artist == other.artist && self.title == other.title && self.album == other.album
+    }
+
+    /// How long the track should play to be scrobbled.
+    fn threshold(&self) -> Duration {
+        self.length.map_or(MAX_SCROBBLE_THRESHOLD, |length| {
+            (length / 2).min(MAX_SCROBBLE_THRESHOLD)
+        })
+    }
+}
+
+/// A track which played long enough.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Scrobble {
+    pub track: Track,
+    pub started_at: DateTime<Utc>,
+}
+
+impl QueueEntry for Scrobble {
+    const KIND: &'static str = "scrobble";
+}
+
+/// A scrobbling service.
+pub trait ScrobbleApi: Send + Sync {
+    /// Name of the file to queue the scrobbles in while the service is unreachable.
+    fn queue_file(&self) -> String;
+
+    /// Tells the service the track started playing.
+    fn now_playing<'a>(&'a self, track: &'a Track) -> SinkFuture<'a, Result<(), WatcherError>>;
+
+    fn scrobble<'a>(&'a self, scrobble: &'a Scrobble) -> SinkFuture<'a, Result<(), WatcherError>>;
+}
+
+impl<A: ScrobbleApi> Deliver<Scrobble> for A {
+    fn deliver<'a>(
+        &'a mut self,
+        scrobble: &'a Scrobble,
+    ) -> SinkFuture<'a, Result<(), WatcherError>> {
+        self.scrobble(scrobble)
+    }
+}
+
+/// The track which is currently played by a player.
+struct Play {
+    track: Track,
+    started_at: DateTime<Utc>,
+    /// The end of the latest report, the time before it is already counted.
+    counted_until: DateTime<Utc>,
+    played: Duration,
+    scrobbled: bool,
+}
+
+/// The current play of every player, counting how long its track played.
+#[derive(Default)]
+struct Plays(HashMap<String, Play>);
+
+impl Plays {
+    /// Counts the time the track played.
+    /// Returns the track if it just started playing, and the scrobble if it played long enough.
+    fn observe(&mut self, report: &Report) -> (Option<Track>, Option<Scrobble>) {
+        if report.bucket_type != "currently-playing" {
+            return (None, None);
+        }
+        let Some(track) = Track::from_data(&report.event.data) else {
+            return (None, None);
+        };
+        let started_at = report.event.timestamp;
+
+        // A complete play is reported once it's over.
+        if report.pulsetime.is_none() {
+            let played = report.event.duration.to_std().unwrap_or_default();
+            let scrobble = (played >= track.threshold()).then_some(Scrobble { track, started_at });
+            return (None, scrobble);
+        }
+
+        let mut playing_now = None;
+        let player = track.player.clone();
+        if !self
+            .0
+            .get(&player)
+            .is_some_and(|play| play.track.is_same(&track))
+        {
+            playing_now = Some(track.clone());
+            self.0.insert(
+                player.clone(),
+                Play {
+                    track,
+                    started_at,
+                    counted_until: started_at,
+                    played: Duration::ZERO,
+                    scrobbled: false,
+                },
+            );
+        }
+
+        let play = self.0.get_mut(&player).expect("Play is just inserted");
+        let end = report.end();
+        play.played += (end - started_at.max(play.counted_until))
+            .to_std()
+            .unwrap_or_default();
+        play.counted_until = play.counted_until.max(end);
+
+        let mut scrobble = None;
+        if !play.scrobbled && play.played >= play.track.threshold() {
+            play.scrobbled = true;
+            scrobble = Some(Scrobble {
+                track: play.track.clone(),
+                started_at: play.started_at,
+            });
+        }
+
+        (playing_now, scrobble)
+    }
+}
+
+/// Scrobbles the played tracks to a service, queueing the scrobbles while it's unreachable.
+pub struct ScrobbleSink<A> {
+    api: A,
+    plays: Plays,
+    /// Scrobbles which failed to be submitted, retried in order.
+    queue: Option<Queue<Scrobble>>,
+}
+
+impl<A: ScrobbleApi> ScrobbleSink<A> {
+    pub fn new(api: A, queue_size: usize) -> Self {
+        let queue = if queue_size > 0 {
+            Queue::open_default(&api.queue_file(), queue_size)
+                .map_err(|e| error!("Scrobbles won't be queued while offline: {e:?}"))
+                .ok()
+        } else {
+            None
+        };
+
+        Self::with_queue(api, queue)
+    }
+
+    pub fn with_queue(api: A, queue: Option<Queue<Scrobble>>) -> Self {
+        Self {
+            api,
+            plays: Plays::default(),
+            queue,
+        }
+    }
+
+    async fn send_report(&mut self, report: &Report) -> Result<(), WatcherError> {
+        let (playing_now, scrobble) = self.plays.observe(report);
+        if let Some(track) = playing_now {
+            if let Err(e) = self.api.now_playing(&track).await {
+                warn!("Failed to submit the playing track: {e}");
+            }
+        }
+
+        match scrobble {
+            Some(scrobble) => self.submit(scrobble).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Submits the scrobble, or queues it if the service is unreachable or older scrobbles are waiting.
+    async fn submit(&mut self, scrobble: Scrobble) -> Result<(), WatcherError> {
+        info!(
+            "Scrobbling {} - {}",
+            scrobble.track.artist, scrobble.track.title
+        );
+        match &mut self.queue {
+            Some(queue) => queue.send(scrobble, &mut self.api).await,
+            None => self.api.scrobble(&scrobble).await,
+        }
+    }
+
+    async fn flush_queue(&mut self) -> Result<(), WatcherError> {
+        match &mut self.queue {
+            Some(queue) => queue.flush(&mut self.api).await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl<A: ScrobbleApi> Sink for ScrobbleSink<A> {
+    fn init<'a>(
+        &'a mut self,
+        _buckets: &'a [(String, &'static str)],
+    ) -> SinkFuture<'a, anyhow::Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn send<'a>(&'a mut self, report: &'a Report) -> SinkFuture<'a, Result<(), WatcherError>> {
+        Box::pin(self.send_report(report))
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_, Result<(), WatcherError>> {
+        Box::pin(self.flush_queue())
+    }
+}
+
+/// Name of the queue file for the service at the URL, so that every service has its own queue.
+pub fn queue_file(service: &str, url: &str) -> String {
+    let host = url::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_default();
+
+    format!("{service}-{host}.jsonl")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::sink::test_report::{self, at};
+
+    fn report(title: &str, length: Option<f64>, start: i64, duration: i64) -> Report {
+        test_report::report(
+            json!({ "player": "Spotify", "artist": "The Gathering", "title": title, "length": length }),
+            start,
+            duration,
+            Some(6.0),
+        )
+    }
+
+    #[test]
+    fn scrobbles_after_half_length_or_four_minutes() {
+        let mut plays = Plays::default();
+
+        let (playing_now, scrobble) = plays.observe(&report("A", Some(100.0), 0, 10));
+        assert_eq!(playing_now.unwrap().title, "A");
+        assert!(scrobble.is_none());
+        // Overlapping heartbeats are counted once.
+        assert_eq!(
+            plays.observe(&report("A", Some(100.0), 5, 44)),
+            (None, None)
+        );
+        let (_, scrobble) = plays.observe(&report("A", Some(100.0), 0, 50));
+        assert_eq!(scrobble.unwrap().started_at, at(0));
+        assert_eq!(
+            plays.observe(&report("A", Some(100.0), 0, 90)),
+            (None, None)
+        );
+
+        let (playing_now, scrobble) = plays.observe(&report("B", None, 100, 239));
+        assert!(playing_now.is_some());
+        assert!(scrobble.is_none());
+        let (_, scrobble) = plays.observe(&report("B", None, 100, 240));
+        assert_eq!(scrobble.unwrap().track.title, "B");
+    }
+
+    #[test]
+    fn complete_plays_are_scrobbled_if_long_enough() {
+        let mut plays = Plays::default();
+
+        let mut short = report("A", Some(600.0), 0, 200);
+        short.pulsetime = None;
+        assert_eq!(plays.observe(&short), (None, None));
+
+        let mut long = report("A", Some(600.0), 0, 240);
+        long.pulsetime = None;
+        assert!(plays.observe(&long).1.is_some());
+    }
+}
@@ -0,0 +1,29 @@
+use aw_client_rust::Event as AwEvent;
+use chrono::{DateTime, TimeZone, Utc};
+use serde_json::Value;
+
+use super::Report;
+
+/// Time the given number of seconds after the fixed start of the tests.
+pub fn at(secs: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(1_700_000_000 + secs, 0).unwrap()
+}
+
+/// Report of the media with the data to the "currently-playing" bucket,
+/// starting and lasting for the given seconds.
+pub fn report(data: Value, start_secs: i64, duration_secs: i64, pulsetime: Option<f64>) -> Report {
+    let Value::Object(data) = data else {
+        panic!("Media data must be an object");
+    };
+    Report {
+        bucket: "bucket".to_string(),
+        bucket_type: "currently-playing".to_string(),
+        event: AwEvent {
+            id: None,
+            timestamp: at(start_secs),
+            duration: chrono::Duration::seconds(duration_secs),
+            data,
+        },
+        pulsetime,
+    }
+}
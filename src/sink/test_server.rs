@@ -0,0 +1,101 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// Request received by the stand-in server.
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub path: String,
+    /// Headers with lowercase names.
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+/// Stand-in HTTP server to test the sinks without the real services.
+pub struct TestServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl TestServer {
+    /// Starts the server which gives the responses with the status and the body in order,
+    /// and responds with 200 and an empty JSON object afterwards.
+    pub async fn start(responses: &[(u16, &'static str)]) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let responses = Arc::new(Mutex::new(
+            responses.iter().copied().collect::<VecDeque<_>>(),
+        ));
+
+        let received = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let request = read_request(socket, &responses).await;
+                received.lock().unwrap().push(request);
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn read_request(
+    mut socket: TcpStream,
+    responses: &Mutex<VecDeque<(u16, &'static str)>>,
+) -> Request {
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 4096];
+    let header_end = loop {
+        let read = socket.read(&mut chunk).await.unwrap();
+        buffer.extend_from_slice(&chunk[..read]);
+        if let Some(position) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.lines();
+    let path = lines
+        .next()
+        .and_then(|line| line.split(' ').nth(1))
+        .unwrap_or_default()
+        .to_string();
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let length: usize = headers
+        .get("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    while buffer.len() < header_end + length {
+        let read = socket.read(&mut chunk).await.unwrap();
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+    let request_body = String::from_utf8_lossy(&buffer[header_end..]).to_string();
+
+    let (status, body) = responses.lock().unwrap().pop_front().unwrap_or((200, "{}"));
+    let response = format!(
+        "HTTP/1.1 {status} Status\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    socket.write_all(response.as_bytes()).await.unwrap();
+
+    Request {
+        path,
+        headers,
+        body: request_body,
+    }
+}
@@ -1,72 +1,680 @@
-use std::time::Duration;
+mod error;
+mod tracks;
 
-use crate::platform::MediaData;
-use anyhow::Context;
-use aw_client_rust::{AwClient, Event as AwEvent};
-use chrono::Utc;
+use std::{collections::HashMap, time::Duration};
 
-use super::config::Config;
+pub use error::WatcherError;
+use tracks::Tracks;
 
-const BUCKET_NAME: &str = env!("CARGO_PKG_NAME");
-const TCP_ERROR: &str = "tcp connect error: Connection refused";
+use crate::platform::{MediaData, PlaybackStatus};
+use crate::sink::{
+    print_json, ActivityWatchSink, Audioscrobbler, DryRunSink, FileSink, ListenBrainz, Report,
+    ScrobbleSink, Sink, SinkConfig,
+};
+use aw_client_rust::Event as AwEvent;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use super::config::{Config, PlayerPolicy, Redaction};
+
+/// How the played media is reported to the server.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportMode {
+    /// Heartbeats on every poll, which the server merges into events.
+    #[default]
+    Heartbeat,
+    /// One complete event per play of the media, sent once the media changes or stops.
+    Track,
+}
 
 pub struct Watcher {
-    client: AwClient,
-    bucket_name: String,
+    sinks: Vec<Box<dyn Sink>>,
+    bucket_id: String,
+    hostname: String,
+    /// Whether the time media spends paused is reported to a separate bucket,
+    /// unless the player's policy says otherwise.
+    report_paused: bool,
+    bucket_per_player: bool,
     poll_interval: Duration,
+    redactions: Vec<Redaction>,
+    /// The last successful report for every player and its status, kept after the stream expires
+    /// to tell the media which was already reported.
+    /// Each player is reported as its own heartbeat stream covering the time since its previous report,
+    /// so concurrently playing players don't lose time when their heartbeats interleave in the bucket.
+    streams: HashMap<(String, PlaybackStatus), Stream>,
+    /// Media which is not reported until it plays for the player's minimum duration, in the heartbeat mode.
+    pending: HashMap<(String, PlaybackStatus), Pending>,
+    /// Current plays, if they are reported as complete events.
+    tracks: Option<Tracks>,
+}
+
+struct Stream {
+    reported_at: DateTime<Utc>,
+    data: Map<String, Value>,
+}
+
+impl Stream {
+    /// Whether the media is the same as the one reported by the stream, ignoring the volatile fields.
+    fn is_same_media(&self, data: &Map<String, Value>) -> bool {
+        let significant = |data: &Map<String, Value>| {
+            data.iter()
+                .filter(|(key, _)| !MediaData::VOLATILE_FIELDS.contains(&key.as_str()))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect::<Map<String, Value>>()
+        };
+        significant(&self.data) == significant(data)
+    }
+}
+
+struct Pending {
+    started_at: DateTime<Utc>,
+    /// The time the media was seen last, and the media.
+    stream: Stream,
 }
 
 impl Watcher {
     pub fn new(config: &Config) -> Self {
-        let hostname = gethostname::gethostname().into_string().unwrap();
+        let sinks = if config.dry_run {
+            vec![Box::new(DryRunSink) as Box<dyn Sink>]
+        } else {
+            create_sinks(config)
+        };
 
         Self {
-            client: AwClient::new(&config.host, &config.port.to_string(), BUCKET_NAME),
-            bucket_name: format!("{BUCKET_NAME}_{hostname}"),
+            sinks,
+            bucket_id: config.bucket_id.clone(),
+            hostname: config.hostname.clone(),
+            report_paused: config.report_paused,
+            bucket_per_player: config.bucket_per_player,
             poll_interval: config.poll_interval,
+            redactions: config.redactions.clone(),
+            streams: HashMap::new(),
+            pending: HashMap::new(),
+            tracks: (config.report_mode == ReportMode::Track)
+                .then(|| Tracks::new(config.poll_interval + Duration::from_secs(1))),
         }
     }
 
-    pub async fn init(&self) -> anyhow::Result<()> {
-        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(1));
-        let mut attempts = 0;
-        loop {
-            let f = self
-                .client
-                .create_bucket_simple(&self.bucket_name, "currently-playing");
-            match f.await {
-                Ok(val) => return Ok(val),
-                Err(e) if attempts < 3 && e.to_string().contains(TCP_ERROR) => {
-                    warn!("Failed to connect, retrying: {}", e);
+    /// Prepares the sinks, creating the buckets in advance unless they depend on the player.
+    pub async fn init(&mut self) -> anyhow::Result<()> {
+        let mut buckets = Vec::new();
+        if !self.bucket_per_player {
+            for status in [PlaybackStatus::Playing, PlaybackStatus::Paused] {
+                buckets.extend(self.bucket("", status, self.report_paused, None));
+            }
+        }
+        for sink in &mut self.sinks {
+            sink.init(&buckets).await?;
+        }
 
-                    attempts += 1;
-                    interval.tick().await;
-                }
-                Err(e) => {
-                    return Err(e).context(format!("Failed to create bucket {}", self.bucket_name))
+        Ok(())
+    }
+
+    /// Applies the reloaded configuration, continuing the current heartbeat streams and plays.
+    /// The sinks are recreated only if their settings changed.
+    pub async fn reconfigure(&mut self, config: &Config, previous: &Config) -> anyhow::Result<()> {
+        let mut result = Ok(());
+        if config.report_mode == ReportMode::Heartbeat {
+            if let Err(e) = self.finish_tracks().await {
+                result = Err(e.into());
+            }
+            self.tracks = None;
+        } else {
+            let timeout = config.poll_interval + Duration::from_secs(1);
+            match &mut self.tracks {
+                Some(tracks) => tracks.set_timeout(timeout),
+                None => self.tracks = Some(Tracks::new(timeout)),
+            }
+        }
+
+        self.bucket_id.clone_from(&config.bucket_id);
+        self.hostname.clone_from(&config.hostname);
+        self.report_paused = config.report_paused;
+        self.bucket_per_player = config.bucket_per_player;
+        self.poll_interval = config.poll_interval;
+        self.redactions.clone_from(&config.redactions);
+
+        let sinks_changed = config.sinks != previous.sinks
+            || config.host != previous.host
+            || config.port != previous.port
+            || config.queue_size != previous.queue_size;
+        if sinks_changed && !config.dry_run {
+            info!("Sink settings changed, recreating the sinks");
+            for sink in &mut self.sinks {
+                if let Err(e) = sink.close().await {
+                    result = Err(e.into());
                 }
             }
+            self.sinks = create_sinks(config);
+            self.init().await?;
         }
+
+        result
     }
 
-    pub async fn send_data(&self, data: &MediaData) -> anyhow::Result<()> {
-        let data = data.serialize();
-        info!("Reporting {data:?}");
+    /// Returns the name and the type of the bucket for the player's media, if it's reported.
+    /// The player's own bucket ID replaces the configured one.
+    fn bucket(
+        &self,
+        player: &str,
+        status: PlaybackStatus,
+        report_paused: bool,
+        bucket_id: Option<&str>,
+    ) -> Option<(String, &'static str)> {
+        let id = bucket_id.unwrap_or(&self.bucket_id);
+        let (prefix, bucket_type) = match status {
+            PlaybackStatus::Playing => (id.to_string(), "currently-playing"),
+            PlaybackStatus::Paused if report_paused => (format!("{id}-paused"), "currently-paused"),
+            PlaybackStatus::Paused => return None,
+        };
+        let hostname = &self.hostname;
+        // The player's own bucket is already specific to it.
+        if self.bucket_per_player && bucket_id.is_none() {
+            let player = player_bucket_suffix(player);
+            Some((format!("{prefix}-{player}_{hostname}"), bucket_type))
+        } else {
+            Some((format!("{prefix}_{hostname}"), bucket_type))
+        }
+    }
 
-        let event = AwEvent {
-            id: None,
-            timestamp: Utc::now(),
-            duration: chrono::Duration::zero(),
-            data,
+    /// Reports the media as the player's policy tells.
+    pub async fn send_data(
+        &mut self,
+        data: &MediaData,
+        policy: &PlayerPolicy,
+    ) -> Result<(), WatcherError> {
+        self.send_data_at(data, policy, Utc::now()).await
+    }
+
+    async fn send_data_at(
+        &mut self,
+        data: &MediaData,
+        policy: &PlayerPolicy,
+        now: DateTime<Utc>,
+    ) -> Result<(), WatcherError> {
+        let (bucket_name, bucket_type) = self
+            .bucket(
+                &data.player,
+                data.status,
+                policy.report_paused,
+                policy.bucket_id.as_deref(),
+            )
+            .ok_or_else(|| WatcherError::Bucket {
+                bucket: format!(
+                    "{}-paused",
+                    policy.bucket_id.as_deref().unwrap_or(&self.bucket_id)
+                ),
+                error: None,
+            })?;
+        let stream_key = (data.player.clone(), data.status);
+        let pulsetime = self.poll_interval + Duration::from_secs(1);
+        let serialized = data.serialize(&policy.extra_fields, &self.redactions);
+
+        // The track may have been playing for a while before it's reported first.
+        let mut started_at = now;
+        if data.status == PlaybackStatus::Playing {
+            started_at -= chrono::Duration::from_std(data.elapsed()).unwrap_or_default();
+        }
+
+        if let Some(tracks) = &mut self.tracks {
+            let Some(finished) = tracks.observe(
+                stream_key,
+                serialized,
+                started_at,
+                now,
+                (bucket_name, bucket_type),
+                policy.min_duration,
+            ) else {
+                return Ok(());
+            };
+            return self.submit(&finished).await.1;
+        }
+
+        let last = self.streams.get(&stream_key);
+        let (timestamp, serialized) = match last {
+            // Resend the previously reported data so that the server merges the heartbeats.
+            Some(stream)
+                if stream.is_same_media(&serialized)
+                    && (now - stream.reported_at)
+                        .to_std()
+                        .is_ok_and(|elapsed| elapsed <= pulsetime) =>
+            {
+                (stream.reported_at, stream.data.clone())
+            }
+            _ => {
+                let Some(started_at) = hold_back(
+                    &mut self.pending,
+                    (&stream_key, &serialized),
+                    play_start(last, &serialized, started_at, now),
+                    now,
+                    pulsetime,
+                    policy.min_duration,
+                ) else {
+                    return Ok(());
+                };
+                (started_at, serialized)
+            }
         };
+        info!("Reporting {serialized:?}");
+
+        let report = Report {
+            bucket: bucket_name,
+            bucket_type: bucket_type.to_string(),
+            event: AwEvent {
+                id: None,
+                timestamp,
+                duration: now - timestamp,
+                data: serialized,
+            },
+            pulsetime: Some(pulsetime.as_secs_f64()),
+        };
+        let (accepted, result) = self.submit(&report).await;
+        // A heartbeat accepted by any sink continues the stream.
+        if accepted {
+            self.streams.insert(
+                stream_key,
+                Stream {
+                    reported_at: now,
+                    data: report.event.data,
+                },
+            );
+        }
+
+        result
+    }
 
-        self.client
-            .heartbeat(
-                &self.bucket_name,
-                &event,
-                self.poll_interval.as_secs_f64() + 1.0,
+    /// Prints the media which is filtered out in the dry run, with the reason.
+    pub fn print_filtered(&self, data: &MediaData, policy: &PlayerPolicy, reason: &str) {
+        let bucket = self
+            .bucket(
+                &data.player,
+                data.status,
+                policy.report_paused,
+                policy.bucket_id.as_deref(),
             )
+            .map(|(bucket, _)| bucket);
+        print_json(&json!({
+            "filtered": true,
+            "reason": reason,
+            "bucket": bucket,
+            "data": data.serialize(&policy.extra_fields, &self.redactions),
+        }));
+    }
+
+    /// Reports the plays of the players which were not reported since the previous tick, in the track mode.
+    pub async fn end_tick(&mut self) -> Result<(), WatcherError> {
+        let Some(tracks) = &mut self.tracks else {
+            return Ok(());
+        };
+        let finished = tracks.end_tick(Utc::now());
+
+        self.submit_all(&finished).await
+    }
+
+    /// Reports the current plays and everything the sinks postponed, before exiting.
+    pub async fn finish(&mut self) -> Result<(), WatcherError> {
+        let mut result = self.finish_tracks().await;
+        for sink in &mut self.sinks {
+            if let Err(e) = sink.close().await {
+                result = Err(e);
+            }
+        }
+
+        result
+    }
+
+    /// Reports the current plays as finished, in the track mode.
+    async fn finish_tracks(&mut self) -> Result<(), WatcherError> {
+        let Some(tracks) = &mut self.tracks else {
+            return Ok(());
+        };
+        let finished = tracks.finish_all(Utc::now());
+
+        self.submit_all(&finished).await
+    }
+
+    /// Delivers the reports which the sinks postponed, e.g. while the server was unreachable.
+    pub async fn flush(&mut self) -> Result<(), WatcherError> {
+        let mut result = Ok(());
+        for sink in &mut self.sinks {
+            if let Err(e) = sink.flush().await {
+                result = Err(e);
+            }
+        }
+
+        result
+    }
+
+    async fn submit_all(&mut self, reports: &[Report]) -> Result<(), WatcherError> {
+        let mut result = Ok(());
+        for report in reports {
+            if let (_, Err(e)) = self.submit(report).await {
+                result = Err(e);
+            }
+        }
+
+        result
+    }
+
+    /// Sends the report to every sink.
+    /// Returns whether any sink accepted it, and the last error if any sink failed.
+    async fn submit(&mut self, report: &Report) -> (bool, Result<(), WatcherError>) {
+        let mut accepted = false;
+        let mut result = Ok(());
+        for sink in &mut self.sinks {
+            match sink.send(report).await {
+                Ok(()) => accepted = true,
+                Err(e) => result = Err(e),
+            }
+        }
+
+        (accepted, result)
+    }
+}
+
+/// When the media reported anew started, given when it started by its playback position
+/// and the player's last report. Only the media which differs from the last report is moved back,
+/// not the resumed one, and never before the last report, so that no time is reported twice.
+fn play_start(
+    last: Option<&Stream>,
+    data: &Map<String, Value>,
+    started_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> DateTime<Utc> {
+    match last {
+        None => started_at,
+        Some(last) if last.is_same_media(data) => now.max(last.reported_at),
+        Some(last) => started_at.max(last.reported_at),
+    }
+}
+
+/// Keeps the new media of the player until it plays for the minimum duration.
+/// Returns when it started playing once it's long enough to report, and `None` while it's held back.
+fn hold_back(
+    pending: &mut HashMap<(String, PlaybackStatus), Pending>,
+    (key, data): (&(String, PlaybackStatus), &Map<String, Value>),
+    started_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+    pulsetime: Duration,
+    min_duration: Duration,
+) -> Option<DateTime<Utc>> {
+    let started_at = match pending.remove(key) {
+        Some(held)
+            if held.stream.is_same_media(data)
+                && (now - held.stream.reported_at)
+                    .to_std()
+                    .is_ok_and(|elapsed| elapsed <= pulsetime) =>
+        {
+            started_at.min(held.started_at)
+        }
+        _ => started_at,
+    };
+    if (now - started_at)
+        .to_std()
+        .is_ok_and(|played| played >= min_duration)
+    {
+        return Some(started_at);
+    }
+
+    pending.insert(
+        key.clone(),
+        Pending {
+            started_at,
+            stream: Stream {
+                reported_at: now,
+                data: data.clone(),
+            },
+        },
+    );
+    None
+}
+
+fn create_sinks(config: &Config) -> Vec<Box<dyn Sink>> {
+    config
+        .sinks
+        .iter()
+        .map(|sink| -> Box<dyn Sink> {
+            match sink {
+                SinkConfig::ActivityWatch => Box::new(ActivityWatchSink::new(config)),
+                SinkConfig::File(file) => Box::new(FileSink::new(file.clone())),
+                SinkConfig::ListenBrainz(listenbrainz) => Box::new(ScrobbleSink::new(
+                    ListenBrainz::new(listenbrainz),
+                    config.queue_size,
+                )),
+                SinkConfig::Audioscrobbler(audioscrobbler) => Box::new(ScrobbleSink::new(
+                    Audioscrobbler::new(audioscrobbler),
+                    config.queue_size,
+                )),
+            }
+        })
+        .collect()
+}
+
+/// Converts the player name to a part of the bucket name, e.g. "Microsoft Edge" to "microsoft-edge".
+fn player_bucket_suffix(player: &str) -> String {
+    let mut suffix = String::new();
+    for c in player.chars() {
+        if c.is_alphanumeric() {
+            suffix.extend(c.to_lowercase());
+        } else if !suffix.is_empty() && !suffix.ends_with('-') {
+            suffix.push('-');
+        }
+    }
+    let suffix = suffix.trim_end_matches('-');
+    if suffix.is_empty() {
+        "unknown".to_string()
+    } else {
+        suffix.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    use serde_json::json;
+
+    use crate::sink::test_report::at;
+    use crate::sink::SinkFuture;
+
+    /// Keeps the reports instead of sending them.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<Report>>>);
+
+    impl Sink for Recorder {
+        fn init<'a>(
+            &'a mut self,
+            _buckets: &'a [(String, &'static str)],
+        ) -> SinkFuture<'a, anyhow::Result<()>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn send<'a>(&'a mut self, report: &'a Report) -> SinkFuture<'a, Result<(), WatcherError>> {
+            self.0.lock().unwrap().push(report.clone());
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    fn watcher(recorder: &Recorder) -> Watcher {
+        Watcher {
+            sinks: vec![Box::new(recorder.clone())],
+            bucket_id: "media".to_string(),
+            hostname: "host".to_string(),
+            report_paused: true,
+            bucket_per_player: false,
+            poll_interval: Duration::from_secs(5),
+            redactions: Vec::new(),
+            streams: HashMap::new(),
+            pending: HashMap::new(),
+            tracks: None,
+        }
+    }
+
+    fn policy() -> PlayerPolicy {
+        PlayerPolicy {
+            player: "Podcasts".to_string(),
+            included: true,
+            report_paused: true,
+            min_duration: Duration::ZERO,
+            extra_fields: Vec::new(),
+            bucket_id: None,
+        }
+    }
+
+    /// Sends the media at the time and returns the start and the end of the reported heartbeat.
+    async fn send(
+        watcher: &mut Watcher,
+        recorder: &Recorder,
+        data: &MediaData,
+        now: i64,
+    ) -> (DateTime<Utc>, DateTime<Utc>) {
+        watcher
+            .send_data_at(data, &policy(), at(now))
             .await
-            .map_err(anyhow::Error::from)
+            .unwrap();
+        let report = recorder.0.lock().unwrap().pop().unwrap();
+        (report.event.timestamp, report.end())
+    }
+
+    #[tokio::test]
+    async fn heartbeat_starts_by_position_only_for_new_media() {
+        let recorder = Recorder::default();
+        let mut watcher = watcher(&recorder);
+        let episode = |position, status| MediaData::track("Podcasts", "A", position, status);
+
+        // Seen first mid-track, the media started playing before.
+        let playing = episode(7200, PlaybackStatus::Playing);
+        assert_eq!(
+            send(&mut watcher, &recorder, &playing, 7200).await,
+            (at(0), at(7200))
+        );
+        // The next heartbeat continues the previous one, which the server merges.
+        let playing = episode(7205, PlaybackStatus::Playing);
+        assert_eq!(
+            send(&mut watcher, &recorder, &playing, 7205).await,
+            (at(7200), at(7205))
+        );
+
+        // Resumed after the pause, the media is not moved back over the pause and the reported time.
+        let paused = episode(7205, PlaybackStatus::Paused);
+        send(&mut watcher, &recorder, &paused, 7210).await;
+        let resumed = episode(7206, PlaybackStatus::Playing);
+        assert_eq!(
+            send(&mut watcher, &recorder, &resumed, 7800).await,
+            (at(7800), at(7800))
+        );
+
+        // The next track started after the previous one was reported last.
+        let next = MediaData::track("Podcasts", "B", 20, PlaybackStatus::Playing);
+        assert_eq!(
+            send(&mut watcher, &recorder, &next, 7810).await,
+            (at(7800), at(7810))
+        );
+        let next = MediaData::track("Podcasts", "B", 24, PlaybackStatus::Playing);
+        assert_eq!(
+            send(&mut watcher, &recorder, &next, 7814).await,
+            (at(7810), at(7814))
+        );
+    }
+
+    #[tokio::test]
+    async fn heartbeat_is_held_back_until_minimum_duration() {
+        let recorder = Recorder::default();
+        let mut watcher = watcher(&recorder);
+        let policy = PlayerPolicy {
+            min_duration: Duration::from_secs(10),
+            ..policy()
+        };
+        let mut send = async |title, position, now| {
+            let data = MediaData::track("Podcasts", title, position, PlaybackStatus::Playing);
+            watcher.send_data_at(&data, &policy, at(now)).await.unwrap();
+            recorder.0.lock().unwrap().pop()
+        };
+
+        assert!(send("A", 0, 0).await.is_none());
+        assert!(send("A", 5, 5).await.is_none());
+        // Once it's long enough, the play is reported from its start.
+        let report = send("A", 10, 10).await.unwrap();
+        assert_eq!((report.event.timestamp, report.end()), (at(0), at(10)));
+
+        // The play which changed before the minimum is dropped.
+        assert!(send("B", 0, 15).await.is_none());
+        assert!(send("C", 0, 20).await.is_none());
+        assert!(send("C", 5, 25).await.is_none());
+        let report = send("C", 10, 30).await.unwrap();
+        assert_eq!(report.event.data["title"], json!("C"));
+        assert_eq!((report.event.timestamp, report.end()), (at(20), at(30)));
+        assert!(recorder.0.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn player_bucket_replaces_bucket_per_player() {
+        let recorder = Recorder::default();
+        let mut watcher = watcher(&recorder);
+        watcher.bucket_per_player = true;
+
+        let bucket = |bucket_id, status| {
+            watcher
+                .bucket("Spotify", status, true, bucket_id)
+                .map(|(bucket, _)| bucket)
+        };
+        assert_eq!(
+            bucket(None, PlaybackStatus::Playing).unwrap(),
+            "media-spotify_host"
+        );
+        assert_eq!(
+            bucket(Some("music"), PlaybackStatus::Playing).unwrap(),
+            "music_host"
+        );
+        assert_eq!(
+            bucket(Some("music"), PlaybackStatus::Paused).unwrap(),
+            "music-paused_host"
+        );
+
+        let policy = PlayerPolicy {
+            bucket_id: Some("music".to_string()),
+            ..policy()
+        };
+        let data = MediaData::track("Spotify", "A", 0, PlaybackStatus::Playing);
+        watcher.send_data_at(&data, &policy, at(0)).await.unwrap();
+        let report = recorder.0.lock().unwrap().pop().unwrap();
+        assert_eq!(report.bucket, "music_host");
+    }
+
+    fn stream(data: Value) -> Stream {
+        let Value::Object(data) = data else {
+            panic!("Stream data must be an object");
+        };
+        Stream {
+            reported_at: Utc::now(),
+            data,
+        }
+    }
+
+    #[test]
+    fn same_media_ignores_volatile_fields() {
+        let stream =
+            stream(json!({"player": "Spotify", "title": "A", "position": 10.0, "rate": 1.0}));
+
+        let moved = json!({"player": "Spotify", "title": "A", "position": 15.0, "length": 20.0});
+        assert!(stream.is_same_media(moved.as_object().unwrap()));
+
+        let changed = json!({"player": "Spotify", "title": "B", "position": 15.0});
+        assert!(!stream.is_same_media(changed.as_object().unwrap()));
+    }
+
+    #[test]
+    fn player_bucket_suffix_is_slug() {
+        assert_eq!(player_bucket_suffix("Spotify"), "spotify");
+        assert_eq!(player_bucket_suffix("Movies & TV"), "movies-tv");
+        assert_eq!(
+            player_bucket_suffix(" VLC media player "),
+            "vlc-media-player"
+        );
+        assert_eq!(player_bucket_suffix("!!!"), "unknown");
     }
 }
@@ -0,0 +1,131 @@
+use reqwest::StatusCode;
+
+#[derive(Debug, thiserror::Error)]
+pub enum WatcherError {
+    /// The server is not running, the address is wrong, or the request timed out.
+    #[error("Server is unreachable: {0}")]
+    Unreachable(reqwest::Error),
+    #[error("Server responded with {status}: {error}")]
+    Server {
+        status: StatusCode,
+        error: reqwest::Error,
+    },
+    #[error("Bucket {bucket} is not available")]
+    Bucket {
+        bucket: String,
+        error: Option<reqwest::Error>,
+    },
+    #[error("Failed to serialize the request or the response: {0}")]
+    Serialization(reqwest::Error),
+    #[error("Failed to write the queue: {0:#}")]
+    Queue(anyhow::Error),
+    #[error("Failed to write {path}: {error}")]
+    File { path: String, error: std::io::Error },
+    /// An error code of the Audioscrobbler API, which it reports in the response body.
+    #[error("Scrobbling service responded with error {code}: {message}")]
+    Scrobbler { code: u64, message: String },
+}
+
+impl WatcherError {
+    /// Classifies an error of the ActivityWatch client for a request to the bucket.
+    pub fn from_client(error: reqwest::Error, bucket: &str) -> Self {
+        if error.status() == Some(StatusCode::NOT_FOUND) {
+            return WatcherError::Bucket {
+                bucket: bucket.to_string(),
+                error: Some(error),
+            };
+        }
+
+        Self::from_request(error)
+    }
+
+    /// Classifies an error of an HTTP request.
+    pub fn from_request(error: reqwest::Error) -> Self {
+        if let Some(status) = error.status() {
+            return WatcherError::Server { status, error };
+        }
+        if error.is_builder() || error.is_decode() {
+            return WatcherError::Serialization(error);
+        }
+
+        WatcherError::Unreachable(error)
+    }
+
+    /// Whether the same request may succeed later, so it's worth retrying after a delay.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            WatcherError::Unreachable(_) => true,
+            WatcherError::Server { status, .. } => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            // Operation failed, service offline, temporarily unavailable and rate limit exceeded.
+            WatcherError::Scrobbler { code, .. } => matches!(code, 8 | 11 | 16 | 29),
+            WatcherError::Bucket { .. }
+            | WatcherError::Serialization(_)
+            | WatcherError::Queue(_)
+            | WatcherError::File { .. } => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::{io::AsyncWriteExt, net::TcpListener};
+
+    /// Starts a server which responds to the first request with the given status.
+    async fn respond_with(status: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let response = format!("HTTP/1.1 {status}\r\ncontent-length: 0\r\n\r\n");
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        format!("http://{address}")
+    }
+
+    async fn request(url: &str) -> WatcherError {
+        let error = reqwest::Client::new()
+            .get(url)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .unwrap_err();
+        WatcherError::from_client(error, "bucket")
+    }
+
+    #[tokio::test]
+    async fn refused_connection_is_retryable() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+
+        let error = request(&format!("http://{address}")).await;
+        assert!(matches!(error, WatcherError::Unreachable(_)));
+        assert!(error.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn statuses_are_classified() {
+        let error = request(&respond_with("503 Service Unavailable").await).await;
+        assert!(matches!(
+            error,
+            WatcherError::Server {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                ..
+            }
+        ));
+        assert!(error.is_retryable());
+
+        let error = request(&respond_with("400 Bad Request").await).await;
+        assert!(matches!(error, WatcherError::Server { .. }));
+        assert!(!error.is_retryable());
+
+        let error = request(&respond_with("404 Not Found").await).await;
+        assert!(matches!(error, WatcherError::Bucket { .. }));
+        assert!(!error.is_retryable());
+    }
+}
@@ -0,0 +1,325 @@
+use std::collections::{HashMap, HashSet};
+
+use aw_client_rust::Event as AwEvent;
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+
+use super::{play_start, Stream};
+use crate::platform::PlaybackStatus;
+use crate::sink::Report;
+
+type PlayKey = (String, PlaybackStatus);
+
+/// Uninterrupted play of the same media by a player.
+struct Play {
+    started_at: DateTime<Utc>,
+    /// The time the play was seen last, and its media.
+    stream: Stream,
+    bucket: String,
+    bucket_type: &'static str,
+    /// Plays shorter than this are not reported.
+    min_duration: chrono::Duration,
+}
+
+impl Play {
+    /// Turns the play into a complete event, unless it took no time at all or less than the minimum.
+    fn finish(self, ended_at: DateTime<Utc>) -> Option<Report> {
+        let duration = ended_at - self.started_at;
+        if duration <= chrono::Duration::zero() {
+            return None;
+        }
+        if duration < self.min_duration {
+            trace!(
+                "Not reporting {:?} which played for less than {}",
+                self.stream.data,
+                self.min_duration
+            );
+            return None;
+        }
+        info!(
+            "Reporting {:?} from {} to {ended_at}",
+            self.stream.data, self.started_at
+        );
+
+        Some(Report {
+            bucket: self.bucket,
+            bucket_type: self.bucket_type.to_string(),
+            event: AwEvent {
+                id: None,
+                timestamp: self.started_at,
+                duration,
+                data: self.stream.data,
+            },
+            pulsetime: None,
+        })
+    }
+}
+
+/// The current play of every player, reported as one event once the media changes or stops.
+pub struct Tracks {
+    plays: HashMap<PlayKey, Play>,
+    /// Plays observed since the last tick.
+    seen: HashSet<PlayKey>,
+    /// The media of the last finished play of every player, with the time it ended.
+    ended: HashMap<PlayKey, Stream>,
+    /// Longest time between observations of a play before it's considered interrupted,
+    /// e.g. while the computer was asleep.
+    timeout: chrono::Duration,
+}
+
+impl Tracks {
+    pub fn new(timeout: std::time::Duration) -> Self {
+        Self {
+            plays: HashMap::new(),
+            seen: HashSet::new(),
+            ended: HashMap::new(),
+            timeout: chrono::Duration::from_std(timeout).unwrap_or(chrono::Duration::MAX),
+        }
+    }
+
+    pub fn set_timeout(&mut self, timeout: std::time::Duration) {
+        self.timeout = chrono::Duration::from_std(timeout).unwrap_or(chrono::Duration::MAX);
+    }
+
+    /// Records the media observed at `now`, which started playing at `started_at`
+    /// unless the player's previous play is the same media or ended later.
+    /// Returns the event of the player's previous play if it's over.
+    pub fn observe(
+        &mut self,
+        key: PlayKey,
+        data: Map<String, Value>,
+        started_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+        bucket: (String, &'static str),
+        min_duration: std::time::Duration,
+    ) -> Option<Report> {
+        self.seen.insert(key.clone());
+        let started_at = started_at.min(now);
+
+        let mut finished = None;
+        if let Some(play) = self.plays.remove(&key) {
+            let interrupted = now - play.stream.reported_at > self.timeout;
+            if !interrupted && play.stream.is_same_media(&data) {
+                let play = Play {
+                    stream: Stream {
+                        reported_at: now,
+                        data: play.stream.data,
+                    },
+                    ..play
+                };
+                self.plays.insert(key, play);
+                return None;
+            }
+
+            // The previous media played until the current one started, unless it was interrupted.
+            let ended_at = if interrupted {
+                play.stream.reported_at
+            } else {
+                started_at.max(play.started_at)
+            };
+            finished = self.close(key.clone(), play, ended_at);
+        }
+
+        let (bucket, bucket_type) = bucket;
+        self.plays.insert(
+            key.clone(),
+            Play {
+                started_at: play_start(self.ended.get(&key), &data, started_at, now),
+                stream: Stream {
+                    reported_at: now,
+                    data,
+                },
+                bucket,
+                bucket_type,
+                min_duration: chrono::Duration::from_std(min_duration)
+                    .unwrap_or(chrono::Duration::MAX),
+            },
+        );
+
+        finished
+    }
+
+    /// Returns the events of the plays which were not observed since the previous tick.
+    pub fn end_tick(&mut self, now: DateTime<Utc>) -> Vec<Report> {
+        let stopped: Vec<PlayKey> = self
+            .plays
+            .keys()
+            .filter(|key| !self.seen.contains(*key))
+            .cloned()
+            .collect();
+        self.seen.clear();
+
+        stopped
+            .into_iter()
+            .filter_map(|key| {
+                let play = self.plays.remove(&key)?;
+                self.finish(key, play, now)
+            })
+            .collect()
+    }
+
+    /// Returns the events of all current plays, e.g. before exiting.
+    pub fn finish_all(&mut self, now: DateTime<Utc>) -> Vec<Report> {
+        self.seen.clear();
+        let plays: Vec<(PlayKey, Play)> = self.plays.drain().collect();
+
+        plays
+            .into_iter()
+            .filter_map(|(key, play)| self.finish(key, play, now))
+            .collect()
+    }
+
+    /// Finishes the play which stopped at some point since it was seen last.
+    fn finish(&mut self, key: PlayKey, play: Play, now: DateTime<Utc>) -> Option<Report> {
+        let ended_at = now.min(play.stream.reported_at + self.timeout);
+        self.close(key, play, ended_at)
+    }
+
+    /// Finishes the play at the time it ended, remembering it for the player's next play.
+    fn close(&mut self, key: PlayKey, play: Play, ended_at: DateTime<Utc>) -> Option<Report> {
+        self.ended.insert(
+            key,
+            Stream {
+                reported_at: ended_at,
+                data: play.stream.data.clone(),
+            },
+        );
+        play.finish(ended_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::sink::test_report::at;
+
+    fn media(title: &str, position: f64) -> Map<String, Value> {
+        let Value::Object(data) =
+            json!({ "player": "Spotify", "title": title, "position": position })
+        else {
+            unreachable!();
+        };
+        data
+    }
+
+    fn observe(tracks: &mut Tracks, title: &str, started_at: i64, now: i64) -> Option<Report> {
+        tracks.observe(
+            ("Spotify".to_string(), PlaybackStatus::Playing),
+            media(title, 0.0),
+            at(started_at),
+            at(now),
+            ("bucket".to_string(), "currently-playing"),
+            std::time::Duration::ZERO,
+        )
+    }
+
+    #[test]
+    fn reports_play_when_track_changes() {
+        let mut tracks = Tracks::new(std::time::Duration::from_secs(6));
+
+        assert!(observe(&mut tracks, "A", 0, 3).is_none());
+        assert!(observe(&mut tracks, "A", 1, 8).is_none());
+        assert!(tracks.end_tick(at(8)).is_empty());
+
+        let finished = observe(&mut tracks, "B", 12, 13).unwrap();
+        assert_eq!(finished.event.timestamp, at(0));
+        assert_eq!(finished.event.duration, chrono::Duration::seconds(12));
+        assert_eq!(finished.event.data["title"], json!("A"));
+        assert_eq!(finished.event.data["position"], json!(0.0));
+        assert!(finished.pulsetime.is_none());
+    }
+
+    #[test]
+    fn reports_play_when_player_stops() {
+        let mut tracks = Tracks::new(std::time::Duration::from_secs(6));
+
+        observe(&mut tracks, "A", 0, 5);
+        assert!(tracks.end_tick(at(5)).is_empty());
+        let finished = tracks.end_tick(at(9));
+        assert_eq!(finished.len(), 1);
+        assert_eq!(finished[0].event.duration, chrono::Duration::seconds(9));
+
+        // A play which was not seen for too long ended soon after it was seen last.
+        observe(&mut tracks, "B", 20, 25);
+        assert!(tracks.end_tick(at(25)).is_empty());
+        let finished = tracks.finish_all(at(100));
+        assert_eq!(finished[0].event.timestamp, at(20));
+        assert_eq!(finished[0].event.duration, chrono::Duration::seconds(11));
+    }
+
+    #[test]
+    fn interrupted_play_is_reported_separately() {
+        let mut tracks = Tracks::new(std::time::Duration::from_secs(6));
+
+        observe(&mut tracks, "A", 0, 5);
+        let finished = observe(&mut tracks, "A", 0, 100).unwrap();
+        assert_eq!(finished.event.duration, chrono::Duration::seconds(5));
+
+        let finished = tracks.finish_all(at(103)).pop().unwrap();
+        assert_eq!(finished.end(), at(103));
+    }
+
+    #[test]
+    fn interrupted_play_continues_when_seen_again() {
+        let mut tracks = Tracks::new(std::time::Duration::from_secs(6));
+
+        observe(&mut tracks, "A", 0, 5);
+        observe(&mut tracks, "A", 0, 100).unwrap();
+
+        // The play of the same media continues from when it's seen again, not from its position.
+        let finished = tracks.finish_all(at(103)).pop().unwrap();
+        assert_eq!(finished.event.timestamp, at(100));
+        assert_eq!(finished.event.duration, chrono::Duration::seconds(3));
+    }
+
+    #[test]
+    fn play_shorter_than_minimum_is_not_reported() {
+        let mut tracks = Tracks::new(std::time::Duration::from_secs(6));
+        let mut observe = |title: &str, now: i64| {
+            tracks.observe(
+                ("Spotify".to_string(), PlaybackStatus::Playing),
+                media(title, 0.0),
+                at(now),
+                at(now),
+                ("bucket".to_string(), "currently-playing"),
+                std::time::Duration::from_secs(10),
+            )
+        };
+
+        observe("A", 0);
+        observe("A", 5);
+        assert!(observe("B", 9).is_none());
+        observe("B", 15);
+        let finished = observe("C", 19).unwrap();
+        assert_eq!(finished.event.data["title"], json!("B"));
+        assert_eq!(finished.event.timestamp, at(9));
+        assert_eq!(finished.event.duration, chrono::Duration::seconds(10));
+    }
+
+    #[test]
+    fn resumed_or_next_play_starts_after_previous_play() {
+        let mut tracks = Tracks::new(std::time::Duration::from_secs(6));
+
+        observe(&mut tracks, "A", 0, 5);
+        assert!(tracks.end_tick(at(5)).is_empty());
+        let finished = tracks.end_tick(at(10)).pop().unwrap();
+        assert_eq!(finished.event.duration, chrono::Duration::seconds(10));
+
+        // Resumed after a pause, the play doesn't overlap the reported one by its position.
+        assert!(observe(&mut tracks, "A", 0, 600).is_none());
+        let finished = observe(&mut tracks, "B", 603, 605).unwrap();
+        assert_eq!(finished.event.timestamp, at(600));
+        assert_eq!(finished.event.duration, chrono::Duration::seconds(3));
+
+        // The next track starts after the previous one ended, even if its position tells it started earlier.
+        assert!(tracks.end_tick(at(605)).is_empty());
+        let finished = tracks.end_tick(at(620)).pop().unwrap();
+        assert_eq!(finished.event.timestamp, at(603));
+        assert!(observe(&mut tracks, "C", 600, 630).is_none());
+        let finished = tracks.finish_all(at(632)).pop().unwrap();
+        assert_eq!(finished.event.timestamp, at(611));
+    }
+}